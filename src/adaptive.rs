@@ -0,0 +1,64 @@
+use crate::{buffer::RenderBuffer, vector::Vector3};
+
+// Spends more samples on the pixels that are still noisy. Every pixel gets the
+//  minimum, then batches are added until its error is under the threshold or it
+//  reaches the maximum
+#[derive(Debug, Clone)]
+pub struct Adaptive {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    // Relative standard error of the pixel, see progressive::relative_error
+    pub threshold: f32,
+    // Samples added between two checks of the error
+    pub batch_size: u32,
+}
+
+impl Adaptive {
+    pub fn new(min_samples: u32, max_samples: u32, threshold: f32) -> Self {
+        Self {
+            min_samples,
+            max_samples,
+            threshold,
+            batch_size: 4,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveResult {
+    pub buffer: RenderBuffer,
+    // Samples taken by every pixel, row by row from the top left corner
+    pub samples: Vec<u32>,
+    pub min_samples: u32,
+    pub max_samples: u32,
+}
+
+impl AdaptiveResult {
+    pub fn total_samples(&self) -> u64 {
+        self.samples.iter().map(|&samples| samples as u64).sum()
+    }
+
+    // Where the samples went, from blue for the minimum through green to red for
+    //  the maximum
+    pub fn heatmap(&self) -> RenderBuffer {
+        let width = self.buffer.width();
+        let mut heatmap = RenderBuffer::new(width, self.buffer.height());
+        let range = self.max_samples.saturating_sub(self.min_samples).max(1) as f32;
+
+        for (index, &samples) in self.samples.iter().enumerate() {
+            let t = (samples.saturating_sub(self.min_samples) as f32 / range).min(1.0);
+            let color = if t < 0.5 {
+                Vector3::new(0.0, t * 2.0, 1.0 - t * 2.0)
+            } else {
+                Vector3::new(t * 2.0 - 1.0, 2.0 - t * 2.0, 0.0)
+            };
+            heatmap.set(index as u32 % width, index as u32 / width, color);
+        }
+        heatmap
+    }
+}
@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use image::ImageResult;
+
+use crate::{
+    buffer::RenderBuffer,
+    cryptomatte::{Cryptomatte, MatteKind},
+    output::{self, Channel, ExrPrecision},
+    vector::Vector3,
+};
+
+// Arbitrary output variable, a pass rendered next to the beauty image. Everything is
+//  taken at the first hit of the camera ray and is zero where the ray misses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    // Distance along the view direction, the camera looks down the negative z axis
+    Depth,
+    // World space shading normal
+    Normal,
+    // Surface color scaled by the material albedo
+    Albedo,
+    Uv,
+    // Index of the object in the scene plus one, zero is the background
+    ObjectId,
+    // Index of the material in the assets plus one, zero is the background
+    MaterialId,
+    // Light coming straight from the lights
+    Direct,
+    // Light coming through reflections, direct and indirect add up to the beauty image
+    Indirect,
+    // How much of the light is blocked, from 0.0 to 1.0
+    Shadow,
+    // Color seen in the reflection, before it's weighted by the reflectivity
+    Reflection,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Uv,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Shadow,
+        Aov::Reflection,
+    ];
+
+    // Used for the layer and file names
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Shadow => "shadow",
+            Aov::Reflection => "reflection",
+        }
+    }
+
+    // Names of the components that are used, in x, y, z order
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Shadow => &["Y"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::Reflection => &["R", "G", "B"],
+        }
+    }
+
+    // Ids would turn into different ids when averaged, so they keep the first sample
+    pub fn filtered(self) -> bool {
+        !matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+}
+
+// Values of every AOV for a single camera ray
+#[derive(Debug, Clone, Copy)]
+pub struct AovSample {
+    pub depth: f32,
+    pub normal: Vector3,
+    pub albedo: Vector3,
+    pub uv: Vector3,
+    // Indices of what was hit
+    pub object: Option<usize>,
+    pub material: Option<usize>,
+    pub direct: Vector3,
+    pub indirect: Vector3,
+    pub shadow: f32,
+    pub reflection: Vector3,
+}
+
+impl AovSample {
+    // What a ray that hits nothing gets
+    pub fn empty() -> Self {
+        Self {
+            depth: 0.0,
+            normal: Vector3::zero(),
+            albedo: Vector3::zero(),
+            uv: Vector3::zero(),
+            object: None,
+            material: None,
+            direct: Vector3::zero(),
+            indirect: Vector3::zero(),
+            shadow: 0.0,
+            reflection: Vector3::zero(),
+        }
+    }
+
+    // Single values are copied to all components so they show up grey
+    pub fn value(&self, aov: Aov) -> Vector3 {
+        let splat = |value: f32| Vector3::new(value, value, value);
+        let id = |index: Option<usize>| index.map_or(0.0, |index| (index + 1) as f32);
+        match aov {
+            Aov::Depth => splat(self.depth),
+            Aov::Normal => self.normal,
+            Aov::Albedo => self.albedo,
+            Aov::Uv => self.uv,
+            Aov::ObjectId => splat(id(self.object)),
+            Aov::MaterialId => splat(id(self.material)),
+            Aov::Direct => self.direct,
+            Aov::Indirect => self.indirect,
+            Aov::Shadow => splat(self.shadow),
+            Aov::Reflection => self.reflection,
+        }
+    }
+}
+
+// Beauty image together with the AOVs and mattes that were asked for
+#[derive(Debug, Clone)]
+pub struct Passes {
+    pub beauty: RenderBuffer,
+    aovs: Vec<(Aov, RenderBuffer)>,
+    cryptomattes: Vec<Cryptomatte>,
+}
+
+impl Passes {
+    pub fn new(
+        beauty: RenderBuffer,
+        aovs: Vec<(Aov, RenderBuffer)>,
+        cryptomattes: Vec<Cryptomatte>,
+    ) -> Self {
+        Self {
+            beauty,
+            aovs,
+            cryptomattes,
+        }
+    }
+
+    pub fn get(&self, aov: Aov) -> Option<&RenderBuffer> {
+        self.aovs
+            .iter()
+            .find(|(other, _)| *other == aov)
+            .map(|(_, buffer)| buffer)
+    }
+
+    pub fn aovs(&self) -> &[(Aov, RenderBuffer)] {
+        &self.aovs
+    }
+
+    pub fn cryptomatte(&self, kind: MatteKind) -> Option<&Cryptomatte> {
+        self.cryptomattes
+            .iter()
+            .find(|cryptomatte| cryptomatte.kind == kind)
+    }
+
+    // Single EXR file, the beauty image goes in R, G and B and every AOV gets its own
+    //  layer, like depth.Z or normal.X. Cryptomattes are only saved here, since they
+    //  need the layers and metadata
+    pub fn save_exr(&self, path: impl AsRef<Path>, precision: ExrPrecision) -> ImageResult<()> {
+        let mut channels = self.beauty.channels(precision);
+        for (aov, buffer) in &self.aovs {
+            let components: [fn(&Vector3) -> f32; 3] = [|v| v.x, |v| v.y, |v| v.z];
+            for (name, component) in aov.channels().iter().zip(components) {
+                channels.push(Channel {
+                    name: format!("{}.{}", aov.name(), name),
+                    values: buffer.pixels().iter().map(component).collect(),
+                    // Half floats can't hold large ids exactly
+                    precision: if aov.filtered() {
+                        precision
+                    } else {
+                        ExrPrecision::Float
+                    },
+                });
+            }
+        }
+
+        let mut attributes = Vec::new();
+        for cryptomatte in &self.cryptomattes {
+            channels.extend(cryptomatte.channels());
+            attributes.extend(cryptomatte.attributes());
+        }
+
+        output::write_exr(
+            path,
+            self.beauty.width(),
+            self.beauty.height(),
+            &channels,
+            &attributes,
+        )
+    }
+
+    // One file per pass, the AOV name goes before the extension, like image.depth.exr
+    pub fn save_separate(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        self.beauty.save(path)?;
+
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        for (aov, buffer) in &self.aovs {
+            buffer.save(path.with_file_name(format!("{}.{}.{}", stem, aov.name(), extension)))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use crate::material::{Material, Texture};
+
+// Handles are only meaningful for the store that created them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId(usize);
+
+impl MaterialId {
+    // Position of the material in the store
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+// Scene level storage for textures and materials, so they can be shared
+//  between objects without duplicating them
+#[derive(Debug, Clone, Default)]
+pub struct Assets {
+    textures: Vec<Texture>,
+    materials: Vec<Material>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_texture(&mut self, texture: Texture) -> TextureId {
+        self.textures.push(texture);
+        TextureId(self.textures.len() - 1)
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    pub fn texture(&self, id: TextureId) -> &Texture {
+        &self.textures[id.0]
+    }
+
+    pub fn material(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    // Names in the order the materials were added, unnamed materials are called
+    //  after their position, like material3
+    pub fn material_names(&self) -> Vec<String> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(index, material)| match &material.name {
+                Some(name) => name.clone(),
+                None => format!("material{}", index),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_assets_hand_back_what_was_added() {
+    use crate::{
+        material::{Coloring, SurfaceKind},
+        vector::Vector2,
+    };
+
+    let mut assets = Assets::new();
+    let image = image::RgbImage::from_pixel(3, 2, image::Rgb([1, 2, 3]));
+    let texture = assets.add_texture(Texture::new(image::DynamicImage::ImageRgb8(image)));
+    let material =
+        |albedo: f32| Material::new(Coloring::Texture(texture), albedo, SurfaceKind::Diffuse);
+    let first = assets.add_material(material(0.25).with_name("first"));
+    let second = assets.add_material(material(0.75));
+
+    assert_ne!(first, second);
+    assert_eq!(assets.material(first).albedo, 0.25);
+    assert_eq!(assets.material(second).albedo, 0.75);
+    assert_eq!(second.index(), 1);
+    assert_eq!(assets.material_names(), ["first", "material1"]);
+
+    // Both materials share the one texture
+    let color = assets
+        .material(second)
+        .color(&assets, Vector2::new(0.5, 0.5));
+    assert_eq!((color.red, color.green, color.blue), (1, 2, 3));
+}
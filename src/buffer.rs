@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use image::{DynamicImage, GenericImage, GenericImageView, ImageResult};
+
+use crate::{
+    material::Color,
+    output::{self, Channel, ExrPrecision},
+    vector::Vector3,
+};
+
+// Linear colors of a render, not clamped so highlights over 1.0 are kept
+#[derive(Debug, Clone)]
+pub struct RenderBuffer {
+    width: u32,
+    height: u32,
+    // Row by row, starting at the top left corner
+    pixels: Vec<Vector3>,
+}
+
+impl RenderBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vector3::zero(); width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Vector3 {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Vector3) {
+        self.pixels[y as usize * self.width as usize + x as usize] = color;
+    }
+
+    pub fn pixels(&self) -> &[Vector3] {
+        &self.pixels
+    }
+
+    // Back from an 8 bit image, what was clipped stays at 1.0
+    pub fn from_image(image: &DynamicImage) -> Self {
+        let mut buffer = Self::new(image.width(), image.height());
+        for (x, y, pixel) in image.pixels() {
+            buffer.set(x, y, Color::from(pixel).linear());
+        }
+        buffer
+    }
+
+    // 8 bit image, everything over 1.0 is clipped
+    pub fn to_image(&self) -> DynamicImage {
+        let mut image = DynamicImage::new_rgb8(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image.put_pixel(x, y, Color::from_linear(self.get(x, y)).to_rgba());
+            }
+        }
+        image
+    }
+
+    // One channel for each of red, green and blue
+    pub fn channels(&self, precision: ExrPrecision) -> Vec<Channel> {
+        let channel = |name: &str, component: fn(&Vector3) -> f32| Channel {
+            name: name.to_string(),
+            values: self.pixels.iter().map(component).collect(),
+            precision,
+        };
+        vec![
+            channel("R", |color| color.x),
+            channel("G", |color| color.y),
+            channel("B", |color| color.z),
+        ]
+    }
+
+    // Picks the format from the extension. EXR, HDR and PFM keep the linear values,
+    //  anything else goes through the 8 bit image
+    pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("exr") => self.save_exr(path, ExrPrecision::Half),
+            Some("hdr") => output::write_hdr(path, self.width, self.height, &self.pixels),
+            Some("pfm") => output::write_pfm(path, self.width, self.height, &self.pixels),
+            _ => self.to_image().save(path),
+        }
+    }
+
+    pub fn save_exr(&self, path: impl AsRef<Path>, precision: ExrPrecision) -> ImageResult<()> {
+        output::write_exr(
+            path,
+            self.width,
+            self.height,
+            &self.channels(precision),
+            &[],
+        )
+    }
+}
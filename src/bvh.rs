@@ -0,0 +1,289 @@
+use crate::{matrix::Matrix4, render::Ray, vector::Vector3};
+
+// Most items that can end up in a leaf
+const LEAF_SIZE: usize = 4;
+
+// Axis aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Self { min, max }
+    }
+
+    // Bounds of a flat circle
+    pub fn disk(center: Vector3, normal: Vector3, radius: f32) -> Self {
+        let extent = Vector3::new(
+            radius * (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+            radius * (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+            radius * (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+        );
+        Self::new(center - extent, center + extent)
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    // Bounds of the box after going through the matrix
+    pub fn transform(&self, matrix: &Matrix4) -> Aabb {
+        let corners = (0..8).map(|i| {
+            matrix.transform_point(Vector3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            ))
+        });
+        corners
+            .map(|corner| Aabb::new(corner, corner))
+            .reduce(|a, b| a.union(&b))
+            .unwrap()
+    }
+
+    // Slab test, whether the ray goes through the box before the given distance
+    pub fn hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.clip(ray, max_distance).is_some()
+    }
+
+    // Part of the ray in front of it and before the given distance that is inside the box
+    pub fn clip(&self, ray: &Ray, max_distance: f32) -> Option<(f32, f32)> {
+        let mut near = 0.0_f32;
+        let mut far = max_distance;
+
+        for (origin, direction, min, max) in [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ] {
+            let inverse = 1.0 / direction;
+            let t1 = (min - origin) * inverse;
+            let t2 = (max - origin) * inverse;
+
+            // NaN happens when the ray is parallel and starts on the slab, min/max ignore it
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+            if far < near {
+                return None;
+            }
+        }
+        Some((near, far))
+    }
+
+    fn axis(&self, axis: usize) -> (f32, f32) {
+        match axis {
+            0 => (self.min.x, self.max.x),
+            1 => (self.min.y, self.max.y),
+            _ => (self.min.z, self.max.z),
+        }
+    }
+}
+
+fn axis_value(vector: Vector3, axis: usize) -> f32 {
+    match axis {
+        0 => vector.x,
+        1 => vector.y,
+        _ => vector.z,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Branch {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
+// Bounding volume hierarchy over a list of items identified by their index.
+//  Items without bounds (like planes) are kept aside and always tested
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(bounds: &[Option<Aabb>]) -> Self {
+        let mut bvh = Self::default();
+        let mut bounded = Vec::new();
+        for (index, item_bounds) in bounds.iter().enumerate() {
+            match item_bounds {
+                Some(item_bounds) => bounded.push((index, *item_bounds)),
+                None => bvh.unbounded.push(index),
+            }
+        }
+
+        if !bounded.is_empty() {
+            bvh.build_node(&mut bounded);
+        }
+        bvh
+    }
+
+    // Splits the items in half along the longest axis of their centroids,
+    //  returns the index of the created node
+    fn build_node(&mut self, items: &mut [(usize, Aabb)]) -> usize {
+        let bounds = items
+            .iter()
+            .map(|(_, bounds)| *bounds)
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+
+        if items.len() <= LEAF_SIZE {
+            let start = self.indices.len();
+            self.indices.extend(items.iter().map(|(index, _)| index));
+            self.nodes.push(Node::Leaf {
+                bounds,
+                start,
+                count: items.len(),
+            });
+            return self.nodes.len() - 1;
+        }
+
+        let centroids = items
+            .iter()
+            .map(|(_, bounds)| {
+                let centroid = bounds.centroid();
+                Aabb::new(centroid, centroid)
+            })
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        let axis = (0..3)
+            .max_by(|a, b| {
+                let (min_a, max_a) = centroids.axis(*a);
+                let (min_b, max_b) = centroids.axis(*b);
+                (max_a - min_a).total_cmp(&(max_b - min_b))
+            })
+            .unwrap();
+
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |a, b| {
+            axis_value(a.1.centroid(), axis).total_cmp(&axis_value(b.1.centroid(), axis))
+        });
+
+        // Reserve the slot so the children come after their parent
+        self.nodes.push(Node::Leaf {
+            bounds,
+            start: 0,
+            count: 0,
+        });
+        let node = self.nodes.len() - 1;
+        let (left_items, right_items) = items.split_at_mut(middle);
+        let left = self.build_node(left_items);
+        let right = self.build_node(right_items);
+        self.nodes[node] = Node::Branch {
+            bounds,
+            left,
+            right,
+        };
+        node
+    }
+
+    // Finds the closest item hit by the ray, the callback gives the distance to an item
+    pub fn closest(
+        &self,
+        ray: &Ray,
+        mut intersect: impl FnMut(usize) -> Option<f32>,
+    ) -> Option<(usize, f32)> {
+        let mut closest: Option<(usize, f32)> = None;
+        let mut test = |index: usize, closest: &mut Option<(usize, f32)>| {
+            if let Some(distance) = intersect(index) {
+                if !matches!(*closest, Some((_, best)) if best <= distance) {
+                    *closest = Some((index, distance));
+                }
+            }
+        };
+
+        for index in &self.unbounded {
+            test(*index, &mut closest);
+        }
+
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let max_distance = closest.map_or(f32::INFINITY, |(_, distance)| distance);
+            if !node.bounds().hit(ray, max_distance) {
+                continue;
+            }
+
+            match node {
+                Node::Leaf { start, count, .. } => {
+                    for index in &self.indices[*start..*start + *count] {
+                        test(*index, &mut closest);
+                    }
+                }
+                Node::Branch { left, right, .. } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+#[test]
+fn test_bvh_builds_around_infinite_bounds() {
+    let unit = |x: f32| {
+        Aabb::new(
+            Vector3::new(x, -1.0, -6.0),
+            Vector3::new(x + 1.0, 1.0, -4.0),
+        )
+    };
+    let infinite = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    // The centroid of an endless box is NaN
+    let bvh = Bvh::build(&[
+        Some(unit(-0.5)),
+        Some(Aabb::new(-infinite, infinite)),
+        Some(unit(3.0)),
+        Some(unit(6.0)),
+        Some(unit(9.0)),
+        Some(unit(12.0)),
+    ]);
+
+    let ray = Ray {
+        origin: Vector3::zero(),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+    let hit = bvh.closest(&ray, |index| (index == 0).then_some(4.0));
+    assert_eq!(hit, Some((0, 4.0)));
+}
@@ -0,0 +1,381 @@
+use std::f32::consts::PI;
+
+use crate::{
+    render::Ray,
+    vector::{Vector2, Vector3},
+};
+
+// Camera sitting at the origin and looking down the negative z axis
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub projection: Projection,
+    // Without a lens it works like a pinhole camera, everything is in focus
+    pub lens: Option<ThinLens>,
+    pub shutter: Shutter,
+    // Renders an image for each eye when set
+    pub stereo: Option<Stereo>,
+}
+
+// How directions around the camera are laid out on the image
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    // Field of view in degrees, along the shorter side of the image
+    Perspective { fov: f32 },
+    // Parallel rays, the view width is how much of the world fits across the image
+    Orthographic { view_width: f32 },
+    // Equidistant fisheye, the field of view in degrees spans the circle that fits
+    //  in the image, it can go past 180
+    Fisheye { fov: f32 },
+    // Full 360 by 180 degree panorama, best with an image twice as wide as tall.
+    //  Like the fisheye it ignores the lens
+    Equirectangular,
+}
+
+// Interval of time the camera is exposed for, moving objects blur over it
+#[derive(Debug, Clone, Copy)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+}
+
+impl Shutter {
+    pub fn new(open: f32, close: f32) -> Self {
+        Self { open, close }
+    }
+
+    // Maps a random number from 0 to 1 to a moment the shutter is open
+    pub fn sample(&self, sample: f32) -> f32 {
+        self.open + (self.close - self.open) * sample
+    }
+}
+
+// Pair of eyes sitting on the x axis, centered on the camera. With the equirectangular
+//  projection it renders omnidirectional stereo for VR
+#[derive(Debug, Clone)]
+pub struct Stereo {
+    pub interocular_distance: f32,
+    // Distance at which both eyes see the same point, it ends up at screen depth. Only
+    //  exact with the perspective projection: fisheye rays away from the center meet
+    //  closer than this, and the orthographic eyes are sheared instead of turned
+    pub convergence_distance: f32,
+    pub layout: StereoLayout,
+}
+
+impl Stereo {
+    pub fn new(interocular_distance: f32, convergence_distance: f32, layout: StereoLayout) -> Self {
+        Self {
+            interocular_distance,
+            convergence_distance,
+            layout,
+        }
+    }
+}
+
+// Where each eye goes in the rendered image, the left eye is always first
+#[derive(Debug, Clone, Copy)]
+pub enum StereoLayout {
+    SideBySide,
+    TopBottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    fn side(self) -> f32 {
+        match self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+// Everything that is picked when casting a camera ray
+#[derive(Debug, Clone, Copy)]
+pub struct CameraSample {
+    // Point of the image, in pixels
+    pub pixel: Vector2,
+    // Point in the unit square, None sends the ray through the center of the lens
+    pub lens: Option<Vector2>,
+    pub time: f32,
+    // None renders from the center of the camera
+    pub eye: Option<Eye>,
+}
+
+// Lens that only keeps in focus what is at the focus distance, the rest is blurred
+//  more the larger the aperture is
+#[derive(Debug, Clone)]
+pub struct ThinLens {
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+    pub aperture: Aperture,
+}
+
+// Shape of the aperture, it gives its shape to the out of focus highlights (bokeh)
+#[derive(Debug, Clone)]
+pub enum Aperture {
+    Circular,
+    // Rotation in degrees
+    Polygonal { blades: u32, rotation: f32 },
+}
+
+impl ThinLens {
+    pub fn new(aperture_radius: f32, focus_distance: f32, aperture: Aperture) -> Self {
+        Self {
+            aperture_radius,
+            focus_distance,
+            aperture,
+        }
+    }
+
+    // Maps a random point in the unit square to a point on the lens
+    pub fn sample(&self, sample: Vector2) -> Vector2 {
+        let point = match self.aperture {
+            Aperture::Circular => concentric_disk(sample),
+            Aperture::Polygonal { blades, rotation } => polygon(sample, blades, rotation),
+        };
+        Vector2::new(
+            point.x * self.aperture_radius,
+            point.y * self.aperture_radius,
+        )
+    }
+}
+
+// Maps the square to the disk keeping the samples evenly spread (Shirley-Chiu)
+fn concentric_disk(sample: Vector2) -> Vector2 {
+    let x = 2.0 * sample.x - 1.0;
+    let y = 2.0 * sample.y - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Vector2::new(0.0, 0.0);
+    }
+
+    let (radius, angle) = if x.abs() > y.abs() {
+        (x, (PI / 4.0) * (y / x))
+    } else {
+        (y, (PI / 2.0) - (PI / 4.0) * (x / y))
+    };
+    Vector2::new(radius * angle.cos(), radius * angle.sin())
+}
+
+// Picks one of the triangles that make up the polygon, then a point inside it
+fn polygon(sample: Vector2, blades: u32, rotation: f32) -> Vector2 {
+    let blades = blades.max(3);
+    let scaled = sample.x * blades as f32;
+    let triangle = (scaled as u32).min(blades - 1);
+    let remapped = scaled - triangle as f32;
+
+    let corner = |index: u32| {
+        let angle = rotation.to_radians() + 2.0 * PI * index as f32 / blades as f32;
+        Vector2::new(angle.cos(), angle.sin())
+    };
+    let (a, b) = (corner(triangle), corner(triangle + 1));
+
+    // Uniform point in the triangle formed with the center
+    let distance = remapped.sqrt();
+    let blend = sample.y;
+    Vector2::new(
+        distance * (a.x * (1.0 - blend) + b.x * blend),
+        distance * (a.y * (1.0 - blend) + b.y * blend),
+    )
+}
+
+impl Camera {
+    // Perspective camera
+    pub fn new(fov: f32) -> Self {
+        Self {
+            projection: Projection::Perspective { fov },
+            lens: None,
+            shutter: Shutter::new(0.0, 0.0),
+            stereo: None,
+        }
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_lens(mut self, lens: ThinLens) -> Self {
+        self.lens = Some(lens);
+        self
+    }
+
+    pub fn with_shutter(mut self, shutter: Shutter) -> Self {
+        self.shutter = shutter;
+        self
+    }
+
+    pub fn with_stereo(mut self, stereo: Stereo) -> Self {
+        self.stereo = Some(stereo);
+        self
+    }
+
+    // Size of the rendered image, with stereo both eyes are put next to each other
+    pub fn image_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.stereo.as_ref().map(|stereo| &stereo.layout) {
+            Some(StereoLayout::SideBySide) => (width * 2, height),
+            Some(StereoLayout::TopBottom) => (width, height * 2),
+            None => (width, height),
+        }
+    }
+
+    // Eye that sees a pixel of the rendered image and the pixel in the image of that eye
+    pub fn eye_pixel(&self, width: u32, height: u32, x: u32, y: u32) -> (Option<Eye>, u32, u32) {
+        match self.stereo.as_ref().map(|stereo| &stereo.layout) {
+            Some(StereoLayout::SideBySide) if x >= width => (Some(Eye::Right), x - width, y),
+            Some(StereoLayout::TopBottom) if y >= height => (Some(Eye::Right), x, y - height),
+            Some(_) => (Some(Eye::Left), x, y),
+            None => (None, x, y),
+        }
+    }
+
+    // Casts a ray through a point of the image, None if the point is not covered by the
+    //  projection, like the corners of a fisheye image
+    pub fn cast_ray(&self, width: u32, height: u32, sample: &CameraSample) -> Option<Ray> {
+        // Distance from the center of the camera to the eye, along the x axis
+        let eye_offset = match (&self.stereo, sample.eye) {
+            (Some(stereo), Some(eye)) => eye.side() * stereo.interocular_distance / 2.0,
+            _ => 0.0,
+        };
+
+        // The lens only works for projections looking down a single axis
+        let (origin, direction, focusable) = match self.projection {
+            Projection::Perspective { fov } => {
+                let sensor = sensor_point(width, height, sample.pixel);
+
+                // Adjust for the fov
+                let fov_adj = (fov.to_radians() / 2.0).tan();
+                let direction = Vector3::new(sensor.x * fov_adj, sensor.y * fov_adj, -1.0);
+                (Vector3::zero(), direction, true)
+            }
+            Projection::Orthographic { view_width } => {
+                let half_width = view_width / 2.0;
+                let half_height = half_width * height as f32 / width as f32;
+                let origin = Vector3::new(
+                    (2.0 * sample.pixel.x / width as f32 - 1.0) * half_width,
+                    (1.0 - 2.0 * sample.pixel.y / height as f32) * half_height,
+                    0.0,
+                );
+                (origin, Vector3::new(0.0, 0.0, -1.0), true)
+            }
+            Projection::Fisheye { fov } => {
+                let sensor = sensor_point(width, height, sample.pixel);
+                let radius = sensor.length();
+                if radius > 1.0 {
+                    return None;
+                }
+
+                // The angle from the view direction grows linearly with the distance
+                //  to the center of the image
+                let theta = radius * fov.to_radians() / 2.0;
+                let phi = sensor.y.atan2(sensor.x);
+                let direction = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                );
+                (Vector3::zero(), direction, false)
+            }
+            Projection::Equirectangular => {
+                // Longitude goes all the way around and latitude from pole to pole,
+                //  the center of the image looks down the negative z axis
+                let longitude = (sample.pixel.x / width as f32 - 0.5) * 2.0 * PI;
+                let latitude = (0.5 - sample.pixel.y / height as f32) * PI;
+                let direction = Vector3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                );
+
+                // Omnidirectional stereo, the eyes turn with the view direction so
+                //  every ray leaves tangent to the circle between them
+                let origin = Vector3::new(longitude.cos(), 0.0, longitude.sin()) * eye_offset;
+                return Some(Ray {
+                    origin,
+                    direction: direction.normalize(),
+                    time: sample.time,
+                });
+            }
+        };
+
+        // Moves the eye sideways and aims it so both eyes see the same thing at the
+        //  convergence distance. Objects in front of it come out of the screen
+        let (origin, direction) = match &self.stereo {
+            Some(stereo) if eye_offset != 0.0 => (
+                origin + Vector3::new(eye_offset, 0.0, 0.0),
+                direction - Vector3::new(eye_offset / stereo.convergence_distance, 0.0, 0.0),
+            ),
+            _ => (origin, direction),
+        };
+
+        Some(match (&self.lens, sample.lens) {
+            (Some(lens), Some(lens_sample)) if focusable => {
+                // Every ray going through the lens meets the one through the center
+                //  at the focus plane
+                let focus_point = origin + direction * lens.focus_distance;
+                let lens_point = lens.sample(lens_sample);
+                let origin = origin + Vector3::new(lens_point.x, lens_point.y, 0.0);
+                Ray {
+                    origin,
+                    direction: (focus_point - origin).normalize(),
+                    time: sample.time,
+                }
+            }
+            _ => Ray {
+                origin,
+                direction: direction.normalize(),
+                time: sample.time,
+            },
+        })
+    }
+}
+
+// Point of the image from -1.0 to 1.0 along its shorter side, the longer side goes
+//  further to keep the aspect ratio
+fn sensor_point(width: u32, height: u32, pixel: Vector2) -> Vector2 {
+    let mut sensor_x = pixel.x / width as f32;
+    let mut sensor_y = pixel.y / height as f32;
+
+    // Convert coordinates to -1.0 to 1.0
+    //  and flip y axis
+    sensor_x = 2.0 * sensor_x - 1.0;
+    sensor_y = 1.0 - (2.0 * sensor_y);
+
+    // Adjust for the aspect ratio
+    if width > height {
+        let aspect_ratio = width as f32 / height as f32;
+        sensor_x *= aspect_ratio;
+    } else {
+        let aspect_ratio = height as f32 / width as f32;
+        sensor_y *= aspect_ratio;
+    }
+
+    Vector2::new(sensor_x, sensor_y)
+}
+
+#[test]
+fn test_lens_rays_converge_at_the_focus_distance() {
+    let camera = Camera::new(60.0).with_lens(ThinLens::new(0.5, 4.0, Aperture::Circular));
+    let sample = |pixel: Vector2, lens: Option<Vector2>| CameraSample {
+        pixel,
+        lens,
+        time: 0.0,
+        eye: None,
+    };
+    // Where the ray crosses the focus plane
+    let focus_point = |ray: Ray| ray.origin + ray.direction * (4.0 / -ray.direction.z);
+
+    for pixel in [Vector2::new(8.0, 6.0), Vector2::new(1.5, 10.5)] {
+        let center = focus_point(camera.cast_ray(16, 12, &sample(pixel, None)).unwrap());
+        assert!((center.z + 4.0).abs() < 1e-5);
+
+        for lens in [Vector2::new(0.1, 0.9), Vector2::new(0.8, 0.3)] {
+            let ray = camera.cast_ray(16, 12, &sample(pixel, Some(lens))).unwrap();
+            assert!(ray.origin.length() > 0.1);
+            assert!((focus_point(ray) - center).length() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::output::{Channel, ExrPrecision};
+
+// Id and coverage pairs kept for each pixel, two go in every RGBA layer
+pub const CRYPTOMATTE_RANKS: usize = 6;
+
+// What the mattes are keyed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatteKind {
+    Object,
+    Material,
+}
+
+impl MatteKind {
+    pub fn layer_name(self) -> &'static str {
+        match self {
+            MatteKind::Object => "CryptoObject",
+            MatteKind::Material => "CryptoMaterial",
+        }
+    }
+}
+
+// Coverage of every name in every pixel, stored in the cryptomatte layout: names are
+//  hashed into float ids and each pixel keeps the ids that cover it most
+#[derive(Debug, Clone)]
+pub struct Cryptomatte {
+    pub kind: MatteKind,
+    width: u32,
+    names: Vec<String>,
+    ids: Vec<f32>,
+    // Id and coverage, from most to least covered. Everything with the same name
+    //  shares an entry
+    pixels: Vec<Vec<(f32, f32)>>,
+}
+
+impl Cryptomatte {
+    // The names are indexed by object or material index
+    pub fn new(kind: MatteKind, width: u32, height: u32, names: Vec<String>) -> Self {
+        let ids = names.iter().map(|name| name_to_id(name)).collect();
+        Self {
+            kind,
+            width,
+            names,
+            ids,
+            pixels: vec![Vec::new(); width as usize * height as usize],
+        }
+    }
+
+    // The coverage is given by index into the names
+    pub fn set(&mut self, x: u32, y: u32, coverage: &[(usize, f32)]) {
+        let mut merged: Vec<(f32, f32)> = Vec::new();
+        for &(index, amount) in coverage {
+            let id = self.ids[index];
+            match merged.iter_mut().find(|(merged_id, _)| *merged_id == id) {
+                Some((_, total)) => *total += amount,
+                None => merged.push((id, amount)),
+            }
+        }
+        merged.sort_by(|a, b| b.1.total_cmp(&a.1));
+        self.pixels[y as usize * self.width as usize + x as usize] = merged;
+    }
+
+    // Anti-aliased matte of everything with the given name, from 0.0 to 1.0
+    pub fn matte(&self, name: &str) -> Vec<f32> {
+        let id = name_to_id(name);
+        self.pixels
+            .iter()
+            .map(|coverage| {
+                coverage
+                    .iter()
+                    .filter(|(entry_id, _)| *entry_id == id)
+                    .map(|(_, amount)| amount)
+                    .sum()
+            })
+            .collect()
+    }
+
+    // Layers like CryptoObject00.R, each with two ids and their coverage. Ids need
+    //  the full float precision
+    pub fn channels(&self) -> Vec<Channel> {
+        let mut channels = Vec::new();
+        for rank in 0..CRYPTOMATTE_RANKS {
+            let layer = format!("{}{:02}", self.kind.layer_name(), rank / 2);
+            let (id_channel, coverage_channel) = if rank % 2 == 0 {
+                ("R", "G")
+            } else {
+                ("B", "A")
+            };
+
+            let entry = |coverage: &Vec<(f32, f32)>| coverage.get(rank).copied();
+            channels.push(Channel {
+                name: format!("{}.{}", layer, id_channel),
+                values: self
+                    .pixels
+                    .iter()
+                    .map(|coverage| entry(coverage).map_or(0.0, |(id, _)| id))
+                    .collect(),
+                precision: ExrPrecision::Float,
+            });
+            channels.push(Channel {
+                name: format!("{}.{}", layer, coverage_channel),
+                values: self
+                    .pixels
+                    .iter()
+                    .map(|coverage| entry(coverage).map_or(0.0, |(_, amount)| amount))
+                    .collect(),
+                precision: ExrPrecision::Float,
+            });
+        }
+        channels
+    }
+
+    // Header attributes telling the compositor how to read the layers, including the
+    //  manifest that maps the names to their ids
+    pub fn attributes(&self) -> Vec<(String, String)> {
+        let layer_name = self.kind.layer_name();
+        let key = &format!("{:08x}", murmur3_32(layer_name.as_bytes(), 0))[..7];
+        let prefix = format!("cryptomatte/{}/", key);
+
+        let mut names = self.names.clone();
+        names.sort();
+        names.dedup();
+        let entries: Vec<_> = names
+            .iter()
+            .map(|name| {
+                // The bits of the id, not the plain hash, so readers can turn it back
+                //  into the exact float
+                let id = name_to_id(name).to_bits();
+                format!("\"{}\":\"{:08x}\"", escape_json(name), id)
+            })
+            .collect();
+
+        vec![
+            (format!("{}name", prefix), layer_name.to_string()),
+            (format!("{}hash", prefix), "MurmurHash3_32".to_string()),
+            (
+                format!("{}conversion", prefix),
+                "uint32_to_float32".to_string(),
+            ),
+            (
+                format!("{}manifest", prefix),
+                format!("{{{}}}", entries.join(",")),
+            ),
+        ]
+    }
+}
+
+// Hash of the name as a float that is never infinite, NaN or denormal
+fn name_to_id(name: &str) -> f32 {
+    let hash = murmur3_32(name.as_bytes(), 0);
+    let exponent = ((hash >> 23) & 0xff).clamp(1, 254);
+    f32::from_bits((hash & 0x807f_ffff) | (exponent << 23))
+}
+
+// MurmurHash3 x86 32 bit, the hash the cryptomatte spec uses
+fn murmur3_32(bytes: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut hash = seed;
+    let mut blocks = bytes.chunks_exact(4);
+    for block in &mut blocks {
+        let k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        hash ^= mix(k);
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (index, byte) in tail.iter().enumerate() {
+            k |= (*byte as u32) << (8 * index);
+        }
+        hash ^= mix(k);
+    }
+
+    // Final avalanche
+    hash ^= bytes.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+    hash
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::new();
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            character if (character as u32) < 0x20 => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn test_murmur3_32() {
+    // Reference values of the x86 32 bit variant
+    assert_eq!(murmur3_32(b"", 0), 0);
+    assert_eq!(murmur3_32(b"", 1), 0x514e_28b7);
+    assert_eq!(murmur3_32(b"test", 0), 0xba6b_d213);
+    assert_eq!(murmur3_32(b"Hello, world!", 0), 0xc036_3e43);
+}
+
+#[test]
+fn test_cryptomatte_merges_equal_names() {
+    let names = ["wall", "lamp", "wall"].map(String::from).to_vec();
+    let mut cryptomatte = Cryptomatte::new(MatteKind::Object, 1, 1, names);
+    cryptomatte.set(0, 0, &[(0, 0.3), (1, 0.4), (2, 0.3)]);
+
+    // Both walls together cover more than the lamp, so they take the first rank
+    let channels = cryptomatte.channels();
+    assert_eq!(channels[0].values[0], name_to_id("wall"));
+    assert!((channels[1].values[0] - 0.6).abs() < 1e-6);
+    assert!((cryptomatte.matte("wall")[0] - 0.6).abs() < 1e-6);
+    assert_eq!(cryptomatte.matte("lamp")[0], 0.4);
+}
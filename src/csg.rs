@@ -0,0 +1,300 @@
+use crate::{
+    bvh::Aabb,
+    object::{Intersectable, Mesh, MeshHit, Part},
+    render::Ray,
+};
+
+// How far past a surface the ray is moved to look for the next one
+const CROSSING_BIAS: f32 = 1e-4;
+
+// Most surfaces looked for along a single ray
+const MAX_CROSSINGS: usize = 32;
+
+// Span along a ray that is inside a shape, the ends can be infinite
+#[derive(Debug, Clone, Copy)]
+pub struct Interval<'a> {
+    pub enter: f32,
+    pub exit: f32,
+    // The shapes the ends are on, once the span belongs to a mesh
+    pub enter_part: Option<Part<'a>>,
+    pub exit_part: Option<Part<'a>>,
+}
+
+impl<'a> Interval<'a> {
+    pub fn new(enter: f32, exit: f32) -> Self {
+        Self {
+            enter,
+            exit,
+            enter_part: None,
+            exit_part: None,
+        }
+    }
+
+    pub fn with_part(mut self, part: Part<'a>) -> Self {
+        self.enter_part = Some(part);
+        self.exit_part = Some(part);
+        self
+    }
+
+    // Builds the spans from the distances where the ray crosses the surface, together
+    //  with whether it was going in. Only makes sense for closed shapes
+    pub fn from_crossings(crossings: &[(f32, bool)]) -> Vec<Self> {
+        let mut intervals = Vec::new();
+        let mut enter = None;
+
+        for (index, (distance, entering)) in crossings.iter().enumerate() {
+            match (enter, entering) {
+                (None, true) => enter = Some(*distance),
+                (Some(start), false) => {
+                    intervals.push(Interval::new(start, *distance));
+                    enter = None;
+                }
+                // The ray started inside the shape
+                (None, false) if index == 0 => {
+                    intervals.push(Interval::new(f32::NEG_INFINITY, *distance))
+                }
+                // Grazing hits can report the same side twice, ignore them
+                _ => {}
+            }
+        }
+
+        if let Some(start) = enter {
+            intervals.push(Interval::new(start, f32::INFINITY));
+        }
+        intervals
+    }
+}
+
+// Finds the spans of any shape by looking for the next surface along the ray over and over.
+//  The callback gives the distance to the closest surface of a ray and whether it goes in
+pub fn march_intervals<'a>(
+    ray: &Ray,
+    next: impl Fn(&Ray) -> Option<(f32, bool)>,
+) -> Vec<Interval<'a>> {
+    let mut crossings = Vec::new();
+    let mut offset = 0.0;
+
+    while crossings.len() < MAX_CROSSINGS {
+        let moved = Ray {
+            origin: ray.origin + ray.direction * offset,
+            direction: ray.direction,
+            time: ray.time,
+        };
+        match next(&moved) {
+            Some((distance, entering)) => {
+                crossings.push((offset + distance, entering));
+                offset += distance + CROSSING_BIAS;
+            }
+            None => break,
+        }
+    }
+
+    Interval::from_crossings(&crossings)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // The right shape is carved out of the left one
+    Difference,
+}
+
+impl CsgOperation {
+    fn is_inside(&self, left: bool, right: bool) -> bool {
+        match self {
+            CsgOperation::Union => left || right,
+            CsgOperation::Intersection => left && right,
+            CsgOperation::Difference => left && !right,
+        }
+    }
+}
+
+// Combination of two closed meshes
+#[derive(Debug, Clone)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Box<Mesh>,
+    pub right: Box<Mesh>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Mesh, right: Mesh) -> Self {
+        Self {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    pub fn intersect_intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        combine(
+            self.operation,
+            &self.left.intersect_intervals(ray),
+            &self.right.intersect_intervals(ray),
+        )
+    }
+
+    pub fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        self.intersect(ray).map(|hit| hit.distance)
+    }
+
+    // First surface of the combined shape in front of the ray
+    pub fn intersect(&self, ray: &Ray) -> Option<MeshHit<'_>> {
+        self.intersect_intervals(ray)
+            .iter()
+            .flat_map(|interval| {
+                [
+                    (interval.enter, interval.enter_part),
+                    (interval.exit, interval.exit_part),
+                ]
+            })
+            .find(|(distance, _)| *distance >= 0.0 && distance.is_finite())
+            .and_then(|(distance, part)| {
+                Some(MeshHit {
+                    distance,
+                    part: part?,
+                })
+            })
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        let left = self.left.bounds();
+        let right = self.right.bounds();
+        match self.operation {
+            CsgOperation::Union => Some(left?.union(&right?)),
+            CsgOperation::Intersection => left.or(right),
+            CsgOperation::Difference => left,
+        }
+    }
+}
+
+// Applies the operation to two sorted lists of spans
+pub fn combine<'a>(
+    operation: CsgOperation,
+    left: &[Interval<'a>],
+    right: &[Interval<'a>],
+) -> Vec<Interval<'a>> {
+    // The carved out surface is seen from inside the right shape
+    let right_part = |part: Option<Part<'a>>| match operation {
+        CsgOperation::Difference => part.map(Part::inverted),
+        _ => part,
+    };
+
+    // Every boundary, with the side it belongs to, whether it goes in and its shape
+    let mut events: Vec<(f32, bool, bool, Option<Part>)> = left
+        .iter()
+        .flat_map(|i| {
+            [
+                (i.enter, true, true, i.enter_part),
+                (i.exit, true, false, i.exit_part),
+            ]
+        })
+        .chain(right.iter().flat_map(|i| {
+            [
+                (i.enter, false, true, right_part(i.enter_part)),
+                (i.exit, false, false, right_part(i.exit_part)),
+            ]
+        }))
+        .collect();
+    events.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut intervals = Vec::new();
+    let (mut in_left, mut in_right) = (false, false);
+    let mut enter = None;
+
+    for (distance, is_left, entering, part) in events {
+        let was_inside = operation.is_inside(in_left, in_right);
+        if is_left {
+            in_left = entering;
+        } else {
+            in_right = entering;
+        }
+        let is_inside = operation.is_inside(in_left, in_right);
+
+        match (was_inside, is_inside) {
+            (false, true) => enter = Some((distance, part)),
+            (true, false) => {
+                if let Some((start, enter_part)) = enter.take() {
+                    // Touching shapes can leave empty spans behind
+                    if distance > start {
+                        intervals.push(Interval {
+                            enter: start,
+                            exit: distance,
+                            enter_part,
+                            exit_part: part,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    intervals
+}
+
+#[test]
+fn test_sphere_with_a_hole() {
+    use crate::{object::Sphere, vector::Vector3};
+
+    let csg = Csg::new(
+        CsgOperation::Difference,
+        Mesh::Sphere(Sphere {
+            center: Vector3::new(0.0, 0.0, -5.0),
+            radius: 2.0,
+        }),
+        Mesh::Sphere(Sphere {
+            center: Vector3::new(0.0, 0.0, -3.0),
+            radius: 1.0,
+        }),
+    );
+    let ray = Ray {
+        origin: Vector3::zero(),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+
+    // The front of the big sphere is carved out, so the ray goes through the hole
+    let hit = csg.intersect(&ray).unwrap();
+    assert!((hit.distance - 4.0).abs() < 1e-4);
+
+    // And the normal points into the hole, back towards the ray
+    let surface = hit.surface(&ray);
+    assert!(surface.normal.z > 0.99);
+}
+
+#[test]
+fn test_union_keeps_the_shape_that_was_hit() {
+    use crate::{object::Sphere, vector::Vector3};
+
+    // The right sphere is in front, and a NaN span doesn't stop the sort
+    let csg = Csg::new(
+        CsgOperation::Union,
+        Mesh::Sphere(Sphere {
+            center: Vector3::new(0.0, 0.0, -6.0),
+            radius: 1.0,
+        }),
+        Mesh::Sphere(Sphere {
+            center: Vector3::new(0.0, 0.5, -3.0),
+            radius: 1.0,
+        }),
+    );
+    let ray = Ray {
+        origin: Vector3::zero(),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+    let intervals = combine(
+        CsgOperation::Union,
+        &csg.intersect_intervals(&ray),
+        &[Interval::new(f32::NAN, f32::NAN)],
+    );
+    assert!(!intervals.is_empty());
+
+    let hit = csg.intersect(&ray).unwrap();
+    let surface = hit.surface(&ray);
+    let hit_point = ray.direction * hit.distance;
+    let expected = (hit_point - Vector3::new(0.0, 0.5, -3.0)).normalize();
+    assert!(surface.normal.dot(&expected) > 0.999);
+}
@@ -0,0 +1,267 @@
+use image::DynamicImage;
+
+use crate::{
+    aov::{Aov, Passes},
+    buffer::RenderBuffer,
+    progressive::luminance,
+    vector::Vector3,
+};
+
+// Weights of the 5x5 B-spline kernel along one axis
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// Albedo below this is treated as black, it isn't divided out of the image
+const MIN_ALBEDO: f32 = 0.01;
+
+// Edge avoiding a-trous wavelet filter, like the spatial part of SVGF (Schied 2017).
+//  Every iteration blurs with a 5x5 kernel whose taps are twice as far apart as in
+//  the last one, and stops at edges in the normals, the albedo and at differences in
+//  brightness that are bigger than the noise of the pixel
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+    // Each one doubles the size of the area that gets blurred
+    pub iterations: u32,
+    // How many standard deviations of noise a neighbor can be away and still count
+    pub color_sigma: f32,
+    // Exponent of the dot product of the normals, higher is stricter
+    pub normal_power: f32,
+    // Difference in albedo at which the weight drops to 1/e
+    pub albedo_sigma: f32,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 4.0,
+            normal_power: 128.0,
+            albedo_sigma: 0.1,
+        }
+    }
+
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_color_sigma(mut self, sigma: f32) -> Self {
+        self.color_sigma = sigma;
+        self
+    }
+
+    // Uses the albedo and normal AOVs of the passes when they were rendered
+    pub fn denoise_passes(&self, passes: &Passes) -> RenderBuffer {
+        self.denoise(
+            &passes.beauty,
+            passes.get(Aov::Albedo),
+            passes.get(Aov::Normal),
+        )
+    }
+
+    // For images that already went through Scene::render. They are clipped, so bright
+    //  highlights don't spread as much as with the linear buffer
+    pub fn denoise_image(
+        &self,
+        image: &DynamicImage,
+        albedo: Option<&RenderBuffer>,
+        normal: Option<&RenderBuffer>,
+    ) -> DynamicImage {
+        self.denoise(&RenderBuffer::from_image(image), albedo, normal)
+            .to_image()
+    }
+
+    // Without the guides only the brightness keeps the edges. Panics if a guide isn't
+    //  the same size as the image
+    pub fn denoise(
+        &self,
+        beauty: &RenderBuffer,
+        albedo: Option<&RenderBuffer>,
+        normal: Option<&RenderBuffer>,
+    ) -> RenderBuffer {
+        let width = beauty.width();
+        let height = beauty.height();
+        for guide in [albedo, normal].into_iter().flatten() {
+            assert!(
+                guide.width() == width && guide.height() == height,
+                "the guides must be the same size as the image"
+            );
+        }
+        let albedo = albedo.map(|albedo| albedo.pixels());
+        let normals: Option<Vec<_>> = normal.map(|normal| {
+            normal
+                .pixels()
+                .iter()
+                .map(|normal| match normal.length() {
+                    length if length > 0.0 => *normal * (1.0 / length),
+                    _ => Vector3::zero(),
+                })
+                .collect()
+        });
+
+        // Textures are divided out so they don't get blurred, only the lighting does
+        let demodulation: Vec<_> = (0..beauty.pixels().len())
+            .map(|index| match albedo {
+                Some(albedo) => albedo[index],
+                None => Vector3::new(1.0, 1.0, 1.0),
+            })
+            .map(|albedo| {
+                let channel = |value: f32| if value > MIN_ALBEDO { value } else { 1.0 };
+                Vector3::new(channel(albedo.x), channel(albedo.y), channel(albedo.z))
+            })
+            .collect();
+        let mut colors: Vec<_> = beauty
+            .pixels()
+            .iter()
+            .zip(&demodulation)
+            .map(|(color, albedo)| divide(*color, *albedo))
+            .collect();
+        let mut variance = estimate_variance(&colors, width, height);
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let mut filtered_colors = colors.clone();
+            let mut filtered_variance = variance.clone();
+
+            for y in 0..height as i32 {
+                for x in 0..width as i32 {
+                    let center = y as usize * width as usize + x as usize;
+                    let center_luminance = luminance(colors[center]);
+                    // Brightness difference that counts as an edge, at least the noise
+                    let luminance_scale = self.color_sigma * variance[center].sqrt() + 1e-4;
+
+                    let mut color_sum = Vector3::zero();
+                    let mut variance_sum = 0.0;
+                    let mut weight_sum = 0.0;
+                    for (ky, kernel_y) in KERNEL.iter().enumerate() {
+                        for (kx, kernel_x) in KERNEL.iter().enumerate() {
+                            let sample_x = x + (kx as i32 - 2) * step;
+                            let sample_y = y + (ky as i32 - 2) * step;
+                            if sample_x < 0
+                                || sample_y < 0
+                                || sample_x >= width as i32
+                                || sample_y >= height as i32
+                            {
+                                continue;
+                            }
+                            let sample = sample_y as usize * width as usize + sample_x as usize;
+
+                            let luminance_difference =
+                                (luminance(colors[sample]) - center_luminance).abs();
+                            let mut weight = kernel_x
+                                * kernel_y
+                                * (-luminance_difference / luminance_scale).exp();
+
+                            if let Some(normals) = &normals {
+                                weight *= self.normal_weight(normals[center], normals[sample]);
+                            }
+                            if let Some(albedo) = albedo {
+                                // Gaussian on the distance between the colors, norm is the
+                                //  squared length
+                                let distance_squared = (albedo[sample] - albedo[center]).norm();
+                                weight *= (-distance_squared
+                                    / (self.albedo_sigma * self.albedo_sigma))
+                                    .exp();
+                            }
+
+                            color_sum = color_sum + colors[sample] * weight;
+                            variance_sum += weight * weight * variance[sample];
+                            weight_sum += weight;
+                        }
+                    }
+
+                    // The center always has a weight, so the sum is never zero
+                    filtered_colors[center] = color_sum * (1.0 / weight_sum);
+                    filtered_variance[center] = variance_sum / (weight_sum * weight_sum);
+                }
+            }
+
+            colors = filtered_colors;
+            variance = filtered_variance;
+        }
+
+        let mut denoised = RenderBuffer::new(width, height);
+        for (index, (color, albedo)) in colors.iter().zip(&demodulation).enumerate() {
+            let color = Vector3::new(color.x * albedo.x, color.y * albedo.y, color.z * albedo.z);
+            denoised.set(index as u32 % width, index as u32 / width, color);
+        }
+        denoised
+    }
+
+    // Pixels that missed everything have no normal, they only blend with each other
+    fn normal_weight(&self, center: Vector3, sample: Vector3) -> f32 {
+        match (center.norm() > 0.0, sample.norm() > 0.0) {
+            (true, true) => center.dot(&sample).max(0.0).powf(self.normal_power),
+            (false, false) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn divide(color: Vector3, albedo: Vector3) -> Vector3 {
+    Vector3::new(color.x / albedo.x, color.y / albedo.y, color.z / albedo.z)
+}
+
+// Variance of the luminance in the 3x3 neighborhood of every pixel, as a stand in for
+//  the variance of its samples
+fn estimate_variance(colors: &[Vector3], width: u32, height: u32) -> Vec<f32> {
+    let mut variance = vec![0.0; colors.len()];
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let mut sum = 0.0;
+            let mut squares = 0.0;
+            let mut count = 0.0;
+            for sample_y in (y - 1).max(0)..=(y + 1).min(height as i32 - 1) {
+                for sample_x in (x - 1).max(0)..=(x + 1).min(width as i32 - 1) {
+                    let luminance =
+                        luminance(colors[sample_y as usize * width as usize + sample_x as usize]);
+                    sum += luminance;
+                    squares += luminance * luminance;
+                    count += 1.0;
+                }
+            }
+            let mean = sum / count;
+            variance[y as usize * width as usize + x as usize] =
+                (squares / count - mean * mean).max(0.0);
+        }
+    }
+    variance
+}
+
+#[test]
+fn test_denoiser_keeps_edges_in_the_normals() {
+    // Noisy left half facing one way, flat right half facing another
+    let mut beauty = RenderBuffer::new(8, 8);
+    let mut normal = RenderBuffer::new(8, 8);
+    for y in 0..8 {
+        for x in 0..8 {
+            let noise = if (x + y) % 2 == 0 { 0.1 } else { -0.1 };
+            let (color, facing) = if x < 4 {
+                (0.5 + noise, Vector3::new(0.0, 1.0, 0.0))
+            } else {
+                (0.0, Vector3::new(1.0, 0.0, 0.0))
+            };
+            beauty.set(x, y, Vector3::new(color, color, color));
+            normal.set(x, y, facing);
+        }
+    }
+
+    let denoised = Denoiser::new().denoise(&beauty, None, Some(&normal));
+    for y in 0..8 {
+        assert!((denoised.get(1, y).x - 0.5).abs() < 0.05);
+        assert_eq!(denoised.get(5, y).x, 0.0);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_denoiser_rejects_guides_of_another_size() {
+    let beauty = RenderBuffer::new(4, 4);
+    let normal = RenderBuffer::new(4, 3);
+    Denoiser::new().denoise(&beauty, None, Some(&normal));
+}
@@ -0,0 +1,208 @@
+use std::f32::consts::PI;
+
+use crate::{
+    buffer::RenderBuffer,
+    vector::{Vector2, Vector3},
+};
+
+// Pixels whose weights add up to less than this are left black. Negative lobes can
+//  cancel the weights out, and dividing by what's left would blow the color up
+const MIN_WEIGHT: f32 = 1e-3;
+
+// Reconstruction filter that spreads every sample over the pixels around it. Wider
+//  filters smooth out aliasing, the ones with negative lobes keep the image sharper
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    // Distance in pixels from the sample to the furthest pixel it reaches
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    // Same weight everywhere, with a radius of 0.5 it is the plain average of the
+    //  samples in the pixel
+    Box,
+    Tent,
+    // Higher alpha falls off faster
+    Gaussian { alpha: f32 },
+    // The B and C parameters of Mitchell and Netravali, 1/3 each is the usual choice
+    Mitchell { b: f32, c: f32 },
+    // Windowed sinc with as many lobes as the radius
+    Lanczos,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f32) -> Self {
+        Self { kind, radius }
+    }
+
+    pub fn gaussian(radius: f32) -> Self {
+        Self::new(FilterKind::Gaussian { alpha: 2.0 }, radius)
+    }
+
+    pub fn mitchell(radius: f32) -> Self {
+        Self::new(
+            FilterKind::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            radius,
+        )
+    }
+
+    // The filters are separable, the weight is the product of both axes
+    pub fn weight(&self, offset: Vector2) -> f32 {
+        self.weight_1d(offset.x) * self.weight_1d(offset.y)
+    }
+
+    fn weight_1d(&self, offset: f32) -> f32 {
+        let radius = self.radius.max(f32::EPSILON);
+        let distance = offset.abs();
+        if distance > radius {
+            return 0.0;
+        }
+
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - distance / radius,
+            FilterKind::Gaussian { alpha } => {
+                // Shifted down so it reaches zero at the radius
+                (-alpha * distance * distance).exp() - (-alpha * radius * radius).exp()
+            }
+            FilterKind::Mitchell { b, c } => {
+                // The polynomials are defined from -2 to 2
+                let x = 2.0 * distance / radius;
+                let weight = if x < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b)
+                } else {
+                    (-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                };
+                weight / 6.0
+            }
+            FilterKind::Lanczos => sinc(distance) * sinc(distance / radius),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(FilterKind::Box, 0.5)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Weighted sums of the samples splatted into every pixel. Samples reach the pixels
+//  of their neighbors too, so every pixel is finished only after all the samples of
+//  the image were added
+#[derive(Debug, Clone)]
+pub struct Film {
+    filter: Filter,
+    width: u32,
+    height: u32,
+    sum: Vec<Vector3>,
+    weights: Vec<f32>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Filter) -> Self {
+        let pixels = width as usize * height as usize;
+        Self {
+            filter,
+            width,
+            height,
+            sum: vec![Vector3::zero(); pixels],
+            weights: vec![0.0; pixels],
+        }
+    }
+
+    // The position is in pixels from the top left corner, pixel centers are at .5
+    pub fn add_sample(&mut self, position: Vector2, color: Vector3) {
+        let radius = self.filter.radius;
+        // Pixels at exactly the radius on the far side are left out, so a box of
+        //  radius 0.5 only ever reaches one pixel
+        let range = |center: f32, size: u32| {
+            let first = (center - 0.5 - radius).floor() as i64 + 1;
+            let last = (center - 0.5 + radius).floor() as i64;
+            first.max(0)..=last.min(size as i64 - 1)
+        };
+
+        for y in range(position.y, self.height) {
+            for x in range(position.x, self.width) {
+                let offset =
+                    Vector2::new(position.x - (x as f32 + 0.5), position.y - (y as f32 + 0.5));
+                let weight = self.filter.weight(offset);
+                if weight != 0.0 {
+                    let index = y as usize * self.width as usize + x as usize;
+                    self.sum[index] = self.sum[index] + color * weight;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+
+    // Black where no sample reached. The negative lobes can ring below zero next to
+    //  bright edges, those colors are clamped
+    pub fn get(&self, x: u32, y: u32) -> Vector3 {
+        let index = y as usize * self.width as usize + x as usize;
+        let weight = self.weights[index];
+        if weight < MIN_WEIGHT {
+            return Vector3::zero();
+        }
+        let color = self.sum[index] * (1.0 / weight);
+        Vector3::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0))
+    }
+
+    pub fn buffer(&self) -> RenderBuffer {
+        let mut buffer = RenderBuffer::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                buffer.set(x, y, self.get(x, y));
+            }
+        }
+        buffer
+    }
+}
+
+#[test]
+fn test_box_filter_averages_the_pixel() {
+    let mut film = Film::new(2, 1, Filter::default());
+    film.add_sample(Vector2::new(0.0, 0.5), Vector3::new(1.0, 1.0, 1.0));
+    film.add_sample(Vector2::new(0.9, 0.2), Vector3::new(3.0, 3.0, 3.0));
+    film.add_sample(Vector2::new(1.0, 0.5), Vector3::new(5.0, 5.0, 5.0));
+    assert_eq!(film.get(0, 0).x, 2.0);
+    assert_eq!(film.get(1, 0).x, 5.0);
+}
+
+#[test]
+fn test_filter_weights() {
+    // Mitchell-Netravali with B = C = 1/3 is 8/9 in the middle and 1/18 halfway out
+    let mitchell = Filter::mitchell(2.0);
+    assert!((mitchell.weight_1d(0.0) - 8.0 / 9.0).abs() < 1e-6);
+    assert!((mitchell.weight_1d(1.0) - 1.0 / 18.0).abs() < 1e-6);
+    assert_eq!(mitchell.weight_1d(2.5), 0.0);
+
+    let gaussian = Filter::gaussian(1.5);
+    let edge = (-2.0f32 * 1.5 * 1.5).exp();
+    assert!((gaussian.weight_1d(0.0) - (1.0 - edge)).abs() < 1e-6);
+    assert!((gaussian.weight_1d(1.0) - ((-2.0f32).exp() - edge)).abs() < 1e-6);
+    assert!(gaussian.weight_1d(1.5).abs() < 1e-6);
+
+    // A lone sample in a negative lobe doesn't turn into a huge or negative color
+    let mut film = Film::new(4, 1, mitchell);
+    film.add_sample(Vector2::new(3.2, 0.5), Vector3::new(1.0, 1.0, 1.0));
+    let color = film.get(1, 0);
+    assert!(color.x >= 0.0 && color.x <= 1.0);
+}
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use image::GenericImageView;
+
+use crate::{
+    bvh::Aabb,
+    object::{Intersectable, Shape},
+    render::Ray,
+    vector::{Vector2, Vector3},
+};
+
+// Terrain made out of a grid of heights, every cell is split into two triangles.
+//  The image x axis goes along the world x axis and the image y axis along z
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    // Heights from 0 to 1, shared so cloning the mesh doesn't copy them
+    heights: Arc<Vec<f32>>,
+    columns: usize,
+    rows: usize,
+    // Corner with the lowest coordinates
+    pub origin: Vector3,
+    // Size of the terrain along x and z
+    pub extent: Vector2,
+    // Height of the brightest pixel
+    pub height_scale: f32,
+}
+
+impl Heightfield {
+    // None for images smaller than 2x2, which don't make a single cell, and for
+    //  negative height scales
+    pub fn from_image(
+        image: &image::DynamicImage,
+        origin: Vector3,
+        extent: Vector2,
+        height_scale: f32,
+    ) -> Option<Self> {
+        let (width, height) = (image.width(), image.height());
+        if width < 2 || height < 2 || height_scale < 0.0 || height_scale.is_nan() {
+            return None;
+        }
+
+        // 8 bit images are converted up by shifting, so white would fall short of 1
+        let heights = if image.color().bytes_per_pixel() / image.color().channel_count() == 1 {
+            image
+                .to_luma8()
+                .pixels()
+                .map(|pixel| pixel.0[0] as f32 / u8::MAX as f32)
+                .collect()
+        } else {
+            image
+                .to_luma16()
+                .pixels()
+                .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+                .collect()
+        };
+
+        Some(Self {
+            heights: Arc::new(heights),
+            columns: width as usize,
+            rows: height as usize,
+            origin,
+            extent,
+            height_scale,
+        })
+    }
+
+    fn cell_size(&self) -> Vector2 {
+        Vector2::new(
+            self.extent.x / (self.columns - 1) as f32,
+            self.extent.y / (self.rows - 1) as f32,
+        )
+    }
+
+    // World space position of a sample of the grid
+    fn vertex(&self, column: usize, row: usize) -> Vector3 {
+        let cell = self.cell_size();
+        Vector3::new(
+            self.origin.x + column as f32 * cell.x,
+            self.origin.y + self.heights[row * self.columns + column] * self.height_scale,
+            self.origin.z + row as f32 * cell.y,
+        )
+    }
+
+    // Normal at a sample of the grid, from the slope to its neighbours
+    fn vertex_normal(&self, column: usize, row: usize) -> Vector3 {
+        let left = self.vertex(column.saturating_sub(1), row);
+        let right = self.vertex((column + 1).min(self.columns - 1), row);
+        let back = self.vertex(column, row.saturating_sub(1));
+        let front = self.vertex(column, (row + 1).min(self.rows - 1));
+
+        (front - back).cross(&(right - left)).normalize()
+    }
+
+    // Position of the point in the grid, in cells
+    fn grid_coords(&self, point: Vector3) -> (f32, f32) {
+        let cell = self.cell_size();
+        (
+            (point.x - self.origin.x) / cell.x,
+            (point.z - self.origin.z) / cell.y,
+        )
+    }
+
+    // Closest hit with the two triangles of the cell
+    fn intersect_cell(&self, ray: &Ray, column: usize, row: usize) -> Option<f32> {
+        let v00 = self.vertex(column, row);
+        let v10 = self.vertex(column + 1, row);
+        let v01 = self.vertex(column, row + 1);
+        let v11 = self.vertex(column + 1, row + 1);
+
+        [
+            intersect_triangle(ray, v00, v10, v11),
+            intersect_triangle(ray, v00, v11, v01),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+// Möller–Trumbore, visible from both sides
+fn intersect_triangle(ray: &Ray, a: Vector3, b: Vector3, c: Vector3) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let offset = ray.origin - a;
+    let u = offset.dot(&p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = offset.cross(&edge1);
+    let v = ray.direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(&q) * inverse;
+    if distance < 0.0 {
+        return None;
+    }
+    Some(distance)
+}
+
+impl Intersectable for Heightfield {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let (start, end) = self.bounds()?.clip(ray, f32::INFINITY)?;
+        let cells = (self.columns as i64 - 1, self.rows as i64 - 1);
+
+        // Walk the cells under the ray in order (grid DDA)
+        let (x, z) = self.grid_coords(ray.origin + ray.direction * start);
+        let mut column = (x.floor() as i64).clamp(0, cells.0 - 1);
+        let mut row = (z.floor() as i64).clamp(0, cells.1 - 1);
+
+        let cell = self.cell_size();
+        let step_column = if ray.direction.x >= 0.0 { 1 } else { -1 };
+        let step_row = if ray.direction.z >= 0.0 { 1 } else { -1 };
+
+        // Distance along the ray to cross a whole cell, and to the next cell border
+        let delta_x = (cell.x / ray.direction.x).abs();
+        let delta_z = (cell.y / ray.direction.z).abs();
+        let border = |index: i64, step: i64, coord: f32| {
+            let next = if step > 0 { index + 1 } else { index };
+            next as f32 - coord
+        };
+        let mut next_x = start + (border(column, step_column, x) / ray.direction.x * cell.x).abs();
+        let mut next_z = start + (border(row, step_row, z) / ray.direction.z * cell.y).abs();
+
+        while (0..cells.0).contains(&column) && (0..cells.1).contains(&row) {
+            if let Some(distance) = self.intersect_cell(ray, column as usize, row as usize) {
+                return Some(distance);
+            }
+
+            if next_x.min(next_z) > end {
+                break;
+            }
+            if next_x < next_z {
+                column += step_column;
+                next_x += delta_x;
+            } else {
+                row += step_row;
+                next_z += delta_z;
+            }
+        }
+        None
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.origin,
+            self.origin + Vector3::new(self.extent.x, self.height_scale, self.extent.y),
+        ))
+    }
+}
+
+impl Shape for Heightfield {
+    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
+        // Blend the normals of the corners of the cell so the terrain looks smooth
+        let (x, z) = self.grid_coords(hit_point);
+        let column = (x.floor().max(0.0) as usize).min(self.columns - 2);
+        let row = (z.floor().max(0.0) as usize).min(self.rows - 2);
+        let (tx, tz) = (x - column as f32, z - row as f32);
+
+        let back =
+            self.vertex_normal(column, row) * (1.0 - tx) + self.vertex_normal(column + 1, row) * tx;
+        let front = self.vertex_normal(column, row + 1) * (1.0 - tx)
+            + self.vertex_normal(column + 1, row + 1) * tx;
+        (back * (1.0 - tz) + front * tz).normalize()
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        // The whole texture covers the terrain once, like the height map
+        Vector2::new(
+            (hit_point.x - self.origin.x) / self.extent.x,
+            (hit_point.z - self.origin.z) / self.extent.y,
+        )
+    }
+
+    fn tangent(&self, _hit_point: Vector3) -> Vector3 {
+        Vector3::new(1.0, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+fn test_heightfield(heights: &[u8], columns: u32) -> Heightfield {
+    let image =
+        image::GrayImage::from_raw(columns, heights.len() as u32 / columns, heights.to_vec())
+            .unwrap();
+    Heightfield::from_image(
+        &image::DynamicImage::ImageLuma8(image),
+        Vector3::zero(),
+        Vector2::new(2.0, 2.0),
+        1.0,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_heightfield_rejects_single_rows_and_negative_scales() {
+    let image = image::DynamicImage::ImageLuma8(image::GrayImage::new(4, 1));
+    let size = Vector2::new(1.0, 1.0);
+    assert!(Heightfield::from_image(&image, Vector3::zero(), size, 1.0).is_none());
+
+    let image = image::DynamicImage::ImageLuma8(image::GrayImage::new(2, 2));
+    assert!(Heightfield::from_image(&image, Vector3::zero(), size, -1.0).is_none());
+    assert!(Heightfield::from_image(&image, Vector3::zero(), size, 1.0).is_some());
+}
+
+#[test]
+fn test_heightfield_hits() {
+    let down = |x: f32, z: f32| Ray {
+        origin: Vector3::new(x, 5.0, z),
+        direction: Vector3::new(0.0, -1.0, 0.0),
+        time: 0.0,
+    };
+
+    // Flat 2x2 field halfway up
+    let flat = test_heightfield(&[255, 255, 255, 255], 2);
+    assert!((flat.intersect_distance(&down(0.5, 1.5)).unwrap() - 4.0).abs() < 1e-4);
+    let normal = flat.surface_normal(Vector3::new(0.5, 1.0, 1.5));
+    assert!((normal.y.abs() - 1.0).abs() < 1e-4);
+    assert!(flat.intersect_distance(&down(3.0, 1.0)).is_none());
+
+    // Slope going up along x from 0 to 1, the height at x is x / 2
+    let slope = test_heightfield(&[0, 255, 0, 255], 2);
+    assert!((slope.intersect_distance(&down(1.0, 1.0)).unwrap() - 4.5).abs() < 1e-4);
+    let ray = Ray {
+        origin: Vector3::new(-1.0, 0.25, 1.0),
+        direction: Vector3::new(1.0, 0.0, 0.0),
+        time: 0.0,
+    };
+    assert!((slope.intersect_distance(&ray).unwrap() - 1.5).abs() < 1e-4);
+}
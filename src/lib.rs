@@ -0,0 +1,26 @@
+pub mod adaptive;
+pub mod aov;
+pub mod assets;
+pub mod buffer;
+pub mod bvh;
+pub mod camera;
+pub mod cryptomatte;
+pub mod csg;
+pub mod denoise;
+pub mod filter;
+pub mod heightfield;
+pub mod light;
+pub mod material;
+pub mod matrix;
+pub mod motion;
+pub mod object;
+pub mod occlusion;
+pub mod output;
+pub mod progressive;
+pub mod random;
+pub mod render;
+pub mod sampler;
+pub mod sdf;
+pub mod solver;
+pub mod transform;
+pub mod vector;
@@ -26,7 +26,7 @@ impl Light {
 
     pub fn direction(&self, point: Vector3) -> Vector3 {
         match self {
-            Light::Point(light) => (light.position - point),
+            Light::Point(light) => light.position - point,
             Light::Directional(light) => -light.direction,
         }
         .normalize()
@@ -35,7 +35,7 @@ impl Light {
     pub fn distance(&self, point: Vector3) -> f32 {
         match self {
             Light::Point(light) => (light.position - point).length(),
-            Light::Directional(_) => std::f32::INFINITY,
+            Light::Directional(_) => f32::INFINITY,
         }
     }
 }
@@ -1,22 +1,29 @@
-mod light;
-mod material;
-mod object;
-mod render;
-mod vector;
-
-use image::ImageFormat;
-use light::{DirectionalLight, Light, PointLight};
-use material::{Color, Coloring, Material, SurfaceKind, Texture};
-use object::{Mesh, Object, Plane, Sphere};
-use render::Scene;
-use vector::Vector3;
+use ray::assets::Assets;
+use ray::camera::Camera;
+use ray::filter::Filter;
+use ray::light::{DirectionalLight, Light, PointLight};
+use ray::material::{Color, Coloring, Material, SurfaceKind, Texture, UvTransform};
+use ray::object::{Mesh, Object, Plane, Sphere};
+use ray::occlusion::{Ambient, AmbientOcclusion, Shading};
+use ray::render::Scene;
+use ray::sampler::SamplerKind;
+use ray::vector::{Vector2, Vector3};
 
 fn main() {
-    let texture = image::open("texture.png").unwrap();
+    let mut assets = Assets::new();
+    let texture = Texture::new(image::open("texture.png").unwrap());
+    let floor_texture = assets.add_texture(texture.clone().with_transform(UvTransform::new(
+        Vector2::new(0.25, 0.25),
+        Vector2::new(0.0, 0.0),
+        0.0,
+    )));
+    let texture = assets.add_texture(texture);
+
     let scene = Scene {
         width: 80 * 10,
         height: 60 * 10,
-        fov: 90.0,
+        camera: Camera::new(90.0),
+        samples_per_pixel: 1,
         max_recursion_depth: 4,
         lights: vec![
             Light::Directional(DirectionalLight {
@@ -58,47 +65,47 @@ fn main() {
         ],
         objects: vec![
             Object::new(
-                Material::new(
-                    Coloring::Texture(Texture::new(texture.clone())),
+                assets.add_material(Material::new(
+                    Coloring::Texture(floor_texture),
                     //Coloring::Color(Color::new(160, 160, 160)),
                     0.18,
                     SurfaceKind::Reflective { reflectivity: 0.5 },
                     //SurfaceKind::Diffuse,
-                ),
+                )),
                 Mesh::Plane(Plane {
                     normal: Vector3::new(-0.0, -1.0, -0.0),
                     origin: Vector3::new(0.0, -3.0, 0.0),
                 }),
             ),
             Object::new(
-                Material::new(
+                assets.add_material(Material::new(
                     Coloring::Color(Color::new(90, 160, 220)),
                     0.25,
                     SurfaceKind::Diffuse,
-                ),
+                )),
                 Mesh::Plane(Plane {
                     normal: Vector3::new(0.0, 0.0, -1.0),
                     origin: Vector3::new(0.0, 0.0, -12.0),
                 }),
             ),
             Object::new(
-                Material::new(
+                assets.add_material(Material::new(
                     Coloring::Color(Color::new(150, 10, 20)),
                     0.22,
                     //SurfaceKind::Reflective { reflectivity: 0.75 },
                     SurfaceKind::Diffuse,
-                ),
+                )),
                 Mesh::Sphere(Sphere {
                     center: Vector3::new(-1.75, 0.0, -3.0),
                     radius: 1.0,
                 }),
             ),
             Object::new(
-                Material::new(
-                    Coloring::Texture(Texture::new(texture.clone())),
+                assets.add_material(Material::new(
+                    Coloring::Texture(texture),
                     0.35,
                     SurfaceKind::Diffuse,
-                ),
+                )),
                 //Material::new(Coloring::Color(Color::new(40, 10, 200)), 0.38),
                 Mesh::Sphere(Sphere {
                     center: Vector3::new(1.0, 1.0, -2.0),
@@ -106,35 +113,52 @@ fn main() {
                 }),
             ),
             Object::new(
-                Material::new(
+                assets.add_material(Material::new(
                     Coloring::Color(Color::new(10, 200, 60)),
                     0.3,
                     SurfaceKind::Reflective { reflectivity: 0.9 },
                     //SurfaceKind::Diffuse,
-                ),
+                )),
                 Mesh::Sphere(Sphere {
                     center: Vector3::new(-0.5, 0.75, -6.5),
                     radius: 2.5,
                 }),
             ),
         ],
+        assets,
+        sampler: SamplerKind::default(),
+        filter: Filter::default(),
+        ambient: Some(
+            Ambient::new(Color::new(255, 255, 255), 0.1)
+                .with_occlusion(AmbientOcclusion::new(8, 2.0)),
+        ),
+        shading: Shading::default(),
     };
 
-    let image = scene.render();
-
-    image
-        .save_with_format("image.png", ImageFormat::Png)
-        .unwrap();
+    // The extension picks the format, .exr, .hdr and .pfm keep the unclamped colors
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "image.png".to_string());
+    scene.render_linear().save(path).unwrap();
 }
 
 #[test]
 fn test_can_render_scene() {
     use image::{DynamicImage, GenericImageView};
+    use ray::transform::Transform;
+
+    let mut assets = Assets::new();
+    let material = assets.add_material(Material::new(
+        Coloring::Color(Color::new(60, 60, 60)),
+        0.38,
+        SurfaceKind::Diffuse,
+    ));
 
     let scene = Scene {
         width: 800,
         height: 600,
-        fov: 90.0,
+        camera: Camera::new(90.0),
+        samples_per_pixel: 1,
         max_recursion_depth: 2,
         lights: vec![Light::Directional(DirectionalLight {
             direction: Vector3::new(0.0, 0.0, -1.0),
@@ -142,16 +166,20 @@ fn test_can_render_scene() {
             intensity: 1.0,
         })],
         objects: vec![Object {
-            material: Material::new(
-                Coloring::Color(Color::new(60, 60, 60)),
-                0.38,
-                SurfaceKind::Diffuse,
-            ),
+            name: None,
+            material,
             mesh: Mesh::Sphere(Sphere {
                 center: Vector3::zero(),
                 radius: 1.0,
             }),
+            transform: Transform::identity(),
+            motion: None,
         }],
+        assets,
+        sampler: SamplerKind::default(),
+        filter: Filter::default(),
+        ambient: None,
+        shading: Shading::default(),
     };
 
     let img: DynamicImage = scene.render();
@@ -1,14 +1,32 @@
-use std::ops::{Add, Mul};
+use std::{
+    ops::{Add, Mul},
+    sync::Arc,
+};
 
 use image::{GenericImageView, Pixel, Rgba};
 
-use crate::vector::Vector2;
+use crate::{
+    assets::{Assets, TextureId},
+    vector::{Vector2, Vector3},
+};
 
 #[derive(Debug, Clone)]
 pub struct Material {
+    // Used to pick the material in the compositor, see Assets::material_names
+    pub name: Option<String>,
     pub coloring: Coloring,
     pub albedo: f32,
     pub surface_kind: SurfaceKind,
+    pub surface_detail: Option<SurfaceDetail>,
+}
+
+// Perturbs the shading normal to fake detail on the surface
+#[derive(Debug, Clone)]
+pub enum SurfaceDetail {
+    // Tangent space normals encoded in the rgb channels
+    NormalMap(TextureId),
+    // Grayscale heights, the slope of the texture tilts the normal
+    BumpMap { texture: TextureId, strength: f32 },
 }
 
 #[derive(Debug, Clone)]
@@ -21,16 +39,69 @@ pub enum SurfaceKind {
 impl Material {
     pub fn new(coloring: Coloring, albedo: f32, surface_kind: SurfaceKind) -> Self {
         Self {
+            name: None,
             coloring,
             albedo,
             surface_kind,
+            surface_detail: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_normal_map(mut self, texture: TextureId) -> Self {
+        self.surface_detail = Some(SurfaceDetail::NormalMap(texture));
+        self
+    }
+
+    pub fn with_bump_map(mut self, texture: TextureId, strength: f32) -> Self {
+        self.surface_detail = Some(SurfaceDetail::BumpMap { texture, strength });
+        self
+    }
+
+    // Calculates the normal used for shading from the orthonormal tangent frame
+    //  at the given texture coordinates
+    pub fn shading_normal(
+        &self,
+        assets: &Assets,
+        normal: Vector3,
+        tangent: Vector3,
+        bitangent: Vector3,
+        coords: Vector2,
+    ) -> Vector3 {
+        match &self.surface_detail {
+            None => normal,
+            Some(SurfaceDetail::NormalMap(texture)) => {
+                let color = assets.texture(*texture).color_at(coords);
+                let x = color.red as f32 / 255.0 * 2.0 - 1.0;
+                let y = color.green as f32 / 255.0 * 2.0 - 1.0;
+                let z = color.blue as f32 / 255.0 * 2.0 - 1.0;
+
+                (tangent * x + bitangent * y + normal * z).normalize()
+            }
+            Some(SurfaceDetail::BumpMap { texture, strength }) => {
+                let texture = assets.texture(*texture);
+                let texel = texture.texel_size();
+                let height = texture.height_at(coords);
+                let height_u = texture.height_at(Vector2::new(coords.x + texel.x, coords.y));
+                let height_v = texture.height_at(Vector2::new(coords.x, coords.y + texel.y));
+
+                // Tilt the normal against the slope of the height map
+                let slope_u = (height_u - height) * strength;
+                let slope_v = (height_v - height) * strength;
+
+                (normal - tangent * slope_u - bitangent * slope_v).normalize()
+            }
         }
     }
 
-    pub fn color(&self, coords: Vector2) -> Color {
+    pub fn color(&self, assets: &Assets, coords: Vector2) -> Color {
         match &self.coloring {
             Coloring::Color(color) => *color,
-            Coloring::Texture(texture) => texture.color_at(coords),
+            Coloring::Texture(texture) => assets.texture(*texture).color_at(coords),
         }
     }
 }
@@ -38,34 +109,145 @@ impl Material {
 #[derive(Debug, Clone)]
 pub enum Coloring {
     Color(Color),
-    Texture(Texture),
+    Texture(TextureId),
 }
 
 #[derive(Debug, Clone)]
 pub struct Texture {
-    image: image::DynamicImage,
+    // Shared so that copies with different sampling settings reuse the pixels
+    image: Arc<image::DynamicImage>,
+    pub wrap: WrapMode,
+    pub transform: UvTransform,
+}
+
+// How texture coordinates outside of 0..1 are mapped back onto the image
+#[derive(Debug, Clone, Copy)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+    Border(Color),
+}
+
+// 2D transform applied to the texture coordinates before sampling.
+//  The coordinates are scaled first, then rotated (in degrees) and finally offset
+#[derive(Debug, Clone, Copy)]
+pub struct UvTransform {
+    pub scale: Vector2,
+    pub offset: Vector2,
+    pub rotation: f32,
+}
+
+impl UvTransform {
+    pub fn new(scale: Vector2, offset: Vector2, rotation: f32) -> Self {
+        Self {
+            scale,
+            offset,
+            rotation,
+        }
+    }
+
+    pub fn apply(&self, coords: Vector2) -> Vector2 {
+        let x = coords.x * self.scale.x;
+        let y = coords.y * self.scale.y;
+
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        Vector2::new(
+            x * cos - y * sin + self.offset.x,
+            x * sin + y * cos + self.offset.y,
+        )
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self::new(Vector2::new(1.0, 1.0), Vector2::new(0.0, 0.0), 0.0)
+    }
 }
 
 impl Texture {
     pub fn new(image: image::DynamicImage) -> Self {
-        Self { image }
+        Self {
+            image: Arc::new(image),
+            wrap: WrapMode::Repeat,
+            transform: UvTransform::default(),
+        }
     }
 
-    pub fn color_at(&self, coords: Vector2) -> Color {
-        let texture_x = self.wrap(coords.x, self.image.width() as f32);
-        let texture_y = self.wrap(coords.y, self.image.height() as f32);
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
 
-        Color::from(self.image.get_pixel(texture_x, texture_y).to_rgba())
+    pub fn with_transform(mut self, transform: UvTransform) -> Self {
+        self.transform = transform;
+        self
     }
 
-    fn wrap(&self, value: f32, limit: f32) -> u32 {
-        let coord = (value * limit) % limit;
-        if coord < 0.0 {
-            (coord + limit) as u32
-        } else {
-            coord as u32
+    pub fn color_at(&self, coords: Vector2) -> Color {
+        // Empty images have no pixels to wrap around
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::new(0, 0, 0);
+        }
+
+        let coords = self.transform.apply(coords);
+        let texture_x = self.wrap_coordinate(coords.x, self.image.width());
+        let texture_y = self.wrap_coordinate(coords.y, self.image.height());
+
+        match (texture_x, texture_y, self.wrap) {
+            (Some(x), Some(y), _) => Color::from(self.image.get_pixel(x, y).to_rgba()),
+            (_, _, WrapMode::Border(color)) => color,
+            _ => unreachable!("only the border mode can fall outside of the texture"),
         }
     }
+
+    // Brightness of the texture at the given coordinates, from 0 to 1
+    pub fn height_at(&self, coords: Vector2) -> f32 {
+        let color = self.color_at(coords);
+        (0.2126 * color.red as f32 + 0.7152 * color.green as f32 + 0.0722 * color.blue as f32)
+            / 255.0
+    }
+
+    // Size of a single pixel in the coordinates given to the texture, before the
+    //  transform scales them
+    pub fn texel_size(&self) -> Vector2 {
+        let scale = self.transform.scale;
+        Vector2::new(
+            1.0 / (self.image.width() as f32 * scale.x.abs().max(f32::EPSILON)),
+            1.0 / (self.image.height() as f32 * scale.y.abs().max(f32::EPSILON)),
+        )
+    }
+
+    // Maps a texture coordinate to a pixel index, None if it lands on the border. The
+    //  size can't be zero
+    fn wrap_coordinate(&self, value: f32, size: u32) -> Option<u32> {
+        let limit = size as f32;
+        let last = size - 1;
+        let coord = value * limit;
+
+        let index = match self.wrap {
+            WrapMode::Repeat => coord.rem_euclid(limit) as u32,
+            WrapMode::Clamp => coord.clamp(0.0, limit) as u32,
+            WrapMode::Mirror => {
+                // Every other repetition of the texture is flipped
+                let period = coord.rem_euclid(2.0 * limit);
+                if period < limit {
+                    period as u32
+                } else {
+                    (2.0 * limit - period) as u32
+                }
+            }
+            WrapMode::Border(_) => {
+                if coord < 0.0 || coord >= limit {
+                    return None;
+                }
+                coord as u32
+            }
+        };
+
+        // Floating point error can land exactly on the limit
+        Some(index.min(last))
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -80,16 +262,26 @@ impl Color {
         Color { red, green, blue }
     }
 
-    pub fn to_rgba(&self) -> Rgba<u8> {
-        Rgba::from_channels(self.red, self.green, self.blue, 255)
+    // Channels from 0.0 to 1.0
+    pub fn linear(self) -> Vector3 {
+        Vector3::new(
+            self.red as f32 / 255.0,
+            self.green as f32 / 255.0,
+            self.blue as f32 / 255.0,
+        )
     }
 
-    pub fn _clamp(&self) -> Color {
-        Color {
-            red: self.red.min(255),
-            green: self.green.min(255),
-            blue: self.blue.min(255),
-        }
+    // Anything outside of 0.0 to 1.0 is clipped
+    pub fn from_linear(color: Vector3) -> Color {
+        Color::new(
+            (color.x * 255.0) as u8,
+            (color.y * 255.0) as u8,
+            (color.z * 255.0) as u8,
+        )
+    }
+
+    pub fn to_rgba(self) -> Rgba<u8> {
+        Rgba::from_channels(self.red, self.green, self.blue, 255)
     }
 }
 
@@ -138,3 +330,49 @@ impl Mul<Color> for Color {
         }
     }
 }
+
+#[test]
+fn test_texture_wrap_modes_and_transforms() {
+    // Black on the left half and white on the right one
+    let image = image::RgbImage::from_fn(4, 1, |x, _| {
+        let value = if x < 2 { 0 } else { 255 };
+        image::Rgb([value, value, value])
+    });
+    let texture = Texture::new(image::DynamicImage::ImageRgb8(image));
+    let red = |texture: &Texture, u: f32| texture.color_at(Vector2::new(u, 0.5)).red;
+
+    assert_eq!(red(&texture, 1.1), 0);
+    assert_eq!(red(&texture, -0.1), 255);
+
+    let clamp = texture.clone().with_wrap(WrapMode::Clamp);
+    assert_eq!(red(&clamp, 1.5), 255);
+    assert_eq!(red(&clamp, -0.5), 0);
+
+    let mirror = texture.clone().with_wrap(WrapMode::Mirror);
+    assert_eq!(red(&mirror, 1.1), 255);
+    assert_eq!(red(&mirror, 1.9), 0);
+
+    let border = texture
+        .clone()
+        .with_wrap(WrapMode::Border(Color::new(9, 9, 9)));
+    assert_eq!(red(&border, 1.1), 9);
+    assert_eq!(red(&border, 0.9), 255);
+
+    // Repeated twice across u and moved half a texture over, so every repeat starts
+    //  on the white half
+    let transformed = texture.with_transform(UvTransform::new(
+        Vector2::new(2.0, 1.0),
+        Vector2::new(0.5, 0.0),
+        0.0,
+    ));
+    assert_eq!(red(&transformed, 0.1), 255);
+    assert_eq!(red(&transformed, 0.3), 0);
+
+    // And a 90 degree turn maps u onto v
+    let rotated = UvTransform::new(Vector2::new(1.0, 1.0), Vector2::new(0.0, 0.0), 90.0)
+        .apply(Vector2::new(0.25, 0.0));
+    assert!(rotated.x.abs() < 1e-6 && (rotated.y - 0.25).abs() < 1e-6);
+
+    let empty = Texture::new(image::DynamicImage::ImageRgb8(image::RgbImage::new(0, 0)));
+    assert_eq!(red(&empty, 0.5), 0);
+}
@@ -0,0 +1,168 @@
+use std::ops::Mul;
+
+use crate::vector::Vector3;
+
+// Row major 4x4 matrix, points are treated as column vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vector3) -> Matrix4 {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(scale: Vector3) -> Matrix4 {
+        Matrix4::new([
+            [scale.x, 0.0, 0.0, 0.0],
+            [0.0, scale.y, 0.0, 0.0],
+            [0.0, 0.0, scale.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotates around the x, then y, then z axes by the given angles in degrees,
+    //  same as Vector3::rotate
+    pub fn rotation(degrees: Vector3) -> Matrix4 {
+        let (sin, cos) = degrees.x.to_radians().sin_cos();
+        let x = Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        let (sin, cos) = degrees.y.to_radians().sin_cos();
+        let y = Matrix4::new([
+            [cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        let (sin, cos) = degrees.z.to_radians().sin_cos();
+        let z = Matrix4::new([
+            [cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        z * y * x
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.m[column][row];
+            }
+        }
+        Matrix4::new(m)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut m = self.m;
+        let mut inverse = Matrix4::identity().m;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|a, b| m[*a][column].abs().total_cmp(&m[*b][column].abs()))
+                .unwrap();
+            if m[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for i in 0..4 {
+                m[column][i] *= scale;
+                inverse[column][i] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = m[row][column];
+                for i in 0..4 {
+                    m[row][i] -= factor * m[column][i];
+                    inverse[row][i] -= factor * inverse[column][i];
+                }
+            }
+        }
+
+        Some(Matrix4::new(inverse))
+    }
+
+    pub fn transform_point(&self, point: Vector3) -> Vector3 {
+        let m = &self.m;
+        let w = m[3][0] * point.x + m[3][1] * point.y + m[3][2] * point.z + m[3][3];
+        self.transform_vector(point) * (1.0 / w)
+            + Vector3::new(m[0][3], m[1][3], m[2][3]) * (1.0 / w)
+    }
+
+    // Ignores the translation
+    pub fn transform_vector(&self, vector: Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3::new(
+            m[0][0] * vector.x + m[0][1] * vector.y + m[0][2] * vector.z,
+            m[1][0] * vector.x + m[1][1] * vector.y + m[1][2] * vector.z,
+            m[2][0] * vector.x + m[2][1] * vector.y + m[2][2] * vector.z,
+        )
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|i| self.m[row][i] * rhs.m[i][column]).sum();
+            }
+        }
+        Matrix4::new(m)
+    }
+}
+
+#[test]
+fn test_matrix_inverse() {
+    let matrix = Matrix4::translation(Vector3::new(1.0, -2.0, 3.0))
+        * Matrix4::rotation(Vector3::new(30.0, 45.0, 60.0))
+        * Matrix4::scaling(Vector3::new(2.0, 0.5, 1.5));
+    let product = matrix * matrix.inverse().unwrap();
+
+    for row in 0..4 {
+        for column in 0..4 {
+            let expected = if row == column { 1.0 } else { 0.0 };
+            assert!((product.m[row][column] - expected).abs() < 1e-5);
+        }
+    }
+
+    assert!(Matrix4::scaling(Vector3::new(1.0, 0.0, 1.0))
+        .inverse()
+        .is_none());
+}
@@ -0,0 +1,241 @@
+use crate::{bvh::Aabb, transform::Transform, vector::Vector3};
+
+// Steps taken between two keyframes when bounding a moving object. Rotations don't
+//  move the corners in a straight line, so the steps are padded for the arcs
+const BOUNDS_STEPS: usize = 8;
+
+// Decomposed transform, so it can be blended without shearing the object
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    pub translation: Vector3,
+    // Angles in degrees around the x, y and z axes
+    pub rotation: Vector3,
+    pub scale: Vector3,
+}
+
+impl Placement {
+    pub fn new(translation: Vector3, rotation: Vector3, scale: Vector3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    // Only moves the object
+    pub fn translation(translation: Vector3) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    pub fn lerp(&self, other: &Placement, t: f32) -> Placement {
+        let mix = |a: Vector3, b: Vector3| a + (b - a) * t;
+        Placement {
+            translation: mix(self.translation, other.translation),
+            rotation: mix(self.rotation, other.rotation),
+            scale: mix(self.scale, other.scale),
+        }
+    }
+
+    // Scales, then rotates, then translates. None if the scale is zero on any axis
+    pub fn to_transform(&self) -> Option<Transform> {
+        Some(
+            Transform::identity()
+                .scale(self.scale)?
+                .rotate(self.rotation)
+                .translate(self.translation),
+        )
+    }
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zero(),
+            rotation: Vector3::zero(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub placement: Placement,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, placement: Placement) -> Self {
+        Self { time, placement }
+    }
+}
+
+// Movement of an object over time. Before the first keyframe and after the last one
+//  the object stays still
+#[derive(Debug, Clone)]
+pub struct Motion {
+    // Sorted by time, never empty
+    keyframes: Vec<Keyframe>,
+}
+
+impl Motion {
+    // Moves at a constant speed from one keyframe to the other, usually the shutter
+    //  open and close times. Panics where keyframed would give None
+    pub fn linear(start: Keyframe, end: Keyframe) -> Self {
+        Self::keyframed(vec![start, end]).unwrap()
+    }
+
+    // None if there are no keyframes, or if the scale is zero at any time. The scale is
+    //  blended between keyframes, so it can't change sign from one to the next either
+    pub fn keyframed(mut keyframes: Vec<Keyframe>) -> Option<Self> {
+        if keyframes.is_empty() {
+            return None;
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let components = |scale: Vector3| [scale.x, scale.y, scale.z];
+        let flat = keyframes
+            .iter()
+            .any(|keyframe| components(keyframe.placement.scale).contains(&0.0));
+        let flips = keyframes.windows(2).any(|pair| {
+            let (a, b) = (pair[0].placement.scale, pair[1].placement.scale);
+            components(a)
+                .iter()
+                .zip(components(b))
+                .any(|(a, b)| a.signum() != b.signum())
+        });
+        if flat || flips {
+            return None;
+        }
+        Some(Self { keyframes })
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn placement_at(&self, time: f32) -> Placement {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0].placement;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].placement;
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        a.placement.lerp(&b.placement, t)
+    }
+
+    pub fn transform_at(&self, time: f32) -> Transform {
+        transform_of(&self.placement_at(time))
+    }
+
+    // Bounds covering everywhere the object goes, given its bounds before the motion
+    pub fn bounds(&self, bounds: &Aabb) -> Aabb {
+        let bounds_at = |placement: &Placement| bounds.transform(transform_of(placement).matrix());
+        // Farthest any point of the object gets from the pivot of the rotation and scale
+        let far = |min: f32, max: f32| min.abs().max(max.abs());
+        let radius = Vector3::new(
+            far(bounds.min.x, bounds.max.x),
+            far(bounds.min.y, bounds.max.y),
+            far(bounds.min.z, bounds.max.z),
+        )
+        .length();
+        let largest = |v: Vector3| v.x.abs().max(v.y.abs()).max(v.z.abs());
+
+        let mut total = bounds_at(&self.keyframes[0].placement);
+        for pair in self.keyframes.windows(2) {
+            let (a, b) = (&pair[0].placement, &pair[1].placement);
+            let mut span = bounds_at(a);
+            for step in 1..=BOUNDS_STEPS {
+                let t = step as f32 / BOUNDS_STEPS as f32;
+                span = span.union(&bounds_at(&a.lerp(b, t)));
+            }
+
+            // Between two steps a corner strays from the line joining them by at most
+            //  step^2 / 8 times its acceleration. With the angles turning by `turn`
+            //  radians over the span, the corner at most `reach` from the pivot and
+            //  the scale moving it by `stretch`, that is turn^2 * reach + 2 * turn * stretch
+            let rotation = b.rotation - a.rotation;
+            let turn = (rotation.x.abs() + rotation.y.abs() + rotation.z.abs()).to_radians();
+            let reach = radius * largest(a.scale).max(largest(b.scale));
+            let stretch = radius * largest(b.scale - a.scale);
+            let step = 1.0 / BOUNDS_STEPS as f32;
+            let error = step * step / 8.0 * (turn * turn * reach + 2.0 * turn * stretch);
+
+            let padding = Vector3::new(error, error, error);
+            total = total.union(&Aabb::new(span.min - padding, span.max + padding));
+        }
+        total
+    }
+}
+
+// Keyframed rejects zero scales, and blending scales of the same sign never gets to
+//  zero, so every placement of a motion has a transform
+fn transform_of(placement: &Placement) -> Transform {
+    placement
+        .to_transform()
+        .expect("motion placements never scale by zero")
+}
+
+#[test]
+fn test_motion_holds_still_outside_keyframes() {
+    let motion = Motion::linear(
+        Keyframe::new(0.0, Placement::translation(Vector3::zero())),
+        Keyframe::new(1.0, Placement::translation(Vector3::new(2.0, 0.0, 0.0))),
+    );
+
+    assert_eq!(motion.placement_at(-1.0).translation.x, 0.0);
+    assert_eq!(motion.placement_at(0.25).translation.x, 0.5);
+    assert_eq!(motion.placement_at(3.0).translation.x, 2.0);
+}
+
+#[test]
+fn test_motion_rejects_scales_through_zero() {
+    let scaled = |time: f32, scale: f32| {
+        Keyframe::new(
+            time,
+            Placement::new(
+                Vector3::zero(),
+                Vector3::zero(),
+                Vector3::new(scale, 1.0, 1.0),
+            ),
+        )
+    };
+    assert!(Motion::keyframed(vec![scaled(0.0, 1.0), scaled(1.0, 2.0)]).is_some());
+    assert!(Motion::keyframed(vec![scaled(0.0, 1.0), scaled(1.0, 0.0)]).is_none());
+    assert!(Motion::keyframed(vec![scaled(0.0, 1.0), scaled(1.0, -1.0)]).is_none());
+}
+
+#[test]
+fn test_motion_bounds_cover_a_rotating_box() {
+    use crate::object::{Cuboid, Intersectable};
+
+    // Off the pivot, so its corners sweep wide arcs
+    let cuboid = Cuboid::axis_aligned(Vector3::new(2.0, -0.5, -0.5), Vector3::new(3.0, 0.5, 0.5));
+    let bounds = cuboid.bounds().unwrap();
+    let motion = Motion::linear(
+        Keyframe::new(0.0, Placement::default()),
+        Keyframe::new(
+            1.0,
+            Placement::new(
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 180.0, 30.0),
+                Vector3::new(1.0, 1.0, 1.0),
+            ),
+        ),
+    );
+
+    let total = motion.bounds(&bounds);
+    for step in 0..=1000 {
+        let at = bounds.transform(motion.transform_at(step as f32 / 1000.0).matrix());
+        assert!(at.min.x >= total.min.x && at.min.y >= total.min.y && at.min.z >= total.min.z);
+        assert!(at.max.x <= total.max.x && at.max.y <= total.max.y && at.max.z <= total.max.z);
+    }
+}
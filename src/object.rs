@@ -1,24 +1,144 @@
-use crate::{material::Material, render::Ray, vector::Vector2, Vector3};
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+    assets::{Assets, MaterialId},
+    bvh::{Aabb, Bvh},
+    csg::{combine, march_intervals, Csg, CsgOperation, Interval},
+    heightfield::Heightfield,
+    material::Material,
+    motion::Motion,
+    render::Ray,
+    sdf::Sdf,
+    solver::{solve_quadratic, solve_quartic},
+    transform::Transform,
+    vector::{Vector2, Vector3},
+};
 
 #[derive(Debug, Clone)]
 pub struct Object {
-    pub material: Material,
+    // Used to pick the object in the compositor, see Scene::object_names
+    pub name: Option<String>,
+    pub material: MaterialId,
     pub mesh: Mesh,
+    // The mesh is defined in object space, this places it in the world
+    pub transform: Transform,
+    // Applied in object space before the transform, so keyframed rotations turn the
+    //  object around its own origin. The object is still without it
+    pub motion: Option<Motion>,
 }
 
 impl Object {
-    pub fn new(material: Material, mesh: Mesh) -> Self {
-        Self { material, mesh }
+    pub fn new(material: MaterialId, mesh: Mesh) -> Self {
+        Self {
+            name: None,
+            material,
+            mesh,
+            transform: Transform::identity(),
+            motion: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+
+    // Where the object is at the given time
+    pub fn transform_at(&self, time: f32) -> Transform {
+        match &self.motion {
+            Some(motion) => motion.transform_at(time).then(&self.transform),
+            None => self.transform,
+        }
+    }
+
+    pub fn intersect<'a>(&'a self, ray: &Ray, assets: &'a Assets) -> Option<Intersection<'a>> {
+        let (distance, hit) = self.intersect_mesh(ray)?;
+        Some(self.hit(ray, distance, &hit, assets))
+    }
+
+    // World space bounds over all time, None if the mesh is infinite
+    pub fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.mesh.bounds()?;
+        let bounds = match &self.motion {
+            Some(motion) => motion.bounds(&bounds),
+            None => bounds,
+        };
+        Some(bounds.transform(self.transform.matrix()))
+    }
+
+    // World space distance to the mesh
+    pub fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let (local_ray, scale) = self.transform_at(ray.time).ray_to_local(ray);
+        self.mesh
+            .intersect_distance(&local_ray)
+            .map(|distance| distance * scale)
     }
 
-    pub fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        if let Some(distance) = self.mesh.intersect_distance(ray) {
-            Some(Intersection {
-                distance,
-                object: self.clone(),
-            })
+    // World space distance to the mesh, with where the ray hit it in object space
+    pub fn intersect_mesh(&self, ray: &Ray) -> Option<(f32, MeshHit<'_>)> {
+        let (local_ray, scale) = self.transform_at(ray.time).ray_to_local(ray);
+        self.mesh
+            .intersect(&local_ray)
+            .map(|hit| (hit.distance * scale, hit))
+    }
+
+    // Builds the hit record from a hit found by intersect_mesh
+    pub fn hit<'a>(
+        &'a self,
+        ray: &Ray,
+        distance: f32,
+        hit: &MeshHit,
+        assets: &'a Assets,
+    ) -> Intersection<'a> {
+        let material = assets.material(self.material);
+        let hit_point = ray.origin + (ray.direction * distance);
+
+        // The surface is evaluated in object space and brought back to the world
+        let transform = self.transform_at(ray.time);
+        let (local_ray, _) = transform.ray_to_local(ray);
+        let local = hit.surface(&local_ray);
+        let surface = Surface {
+            normal: transform.normal_to_world(local.normal),
+            texture_coords: local.texture_coords,
+            tangent: transform.vector_to_world(local.tangent),
+        };
+
+        // Keep the normal on the same side as the ray
+        let front_face = surface.normal.dot(&ray.direction) < 0.0;
+        let normal = if front_face {
+            surface.normal
         } else {
-            None
+            -surface.normal
+        };
+
+        // Make sure the tangent frame is orthonormal
+        let tangent = (surface.tangent - normal * normal.dot(&surface.tangent)).normalize();
+        let bitangent = normal.cross(&tangent);
+
+        let shading_normal =
+            material.shading_normal(assets, normal, tangent, bitangent, surface.texture_coords);
+
+        Intersection {
+            distance,
+            object: self,
+            material,
+            hit_point,
+            normal,
+            shading_normal,
+            texture_coords: surface.texture_coords,
+            tangent,
+            bitangent,
+            front_face,
         }
     }
 }
@@ -27,6 +147,64 @@ impl Object {
 pub enum Mesh {
     Sphere(Sphere),
     Plane(Plane),
+    Box(Cuboid),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Disk(Disk),
+    Annulus(Annulus),
+    Torus(Torus),
+    Group(Arc<Group>),
+    Csg(Csg),
+    Sdf(Sdf),
+    Heightfield(Heightfield),
+}
+
+// Meshes stored together so they can be shared between objects without copying them.
+//  Every object using the group is an instance with its own transform and material
+#[derive(Debug)]
+pub struct Group {
+    meshes: Vec<Mesh>,
+    bvh: Bvh,
+    bounds: Option<Aabb>,
+}
+
+impl Group {
+    pub fn new(meshes: Vec<Mesh>) -> Self {
+        let mesh_bounds: Vec<Option<Aabb>> = meshes.iter().map(|m| m.bounds()).collect();
+        let bounds = mesh_bounds
+            .iter()
+            .copied()
+            .reduce(|a, b| Some(a?.union(&b?)))
+            .flatten();
+
+        Self {
+            bvh: Bvh::build(&mesh_bounds),
+            meshes,
+            bounds,
+        }
+    }
+
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    fn closest_hit(&self, ray: &Ray) -> Option<(usize, f32)> {
+        self.bvh
+            .closest(ray, |index| self.meshes[index].intersect_distance(ray))
+    }
+
+    // Same as closest_hit, but keeps the shape that was hit
+    fn intersect(&self, ray: &Ray) -> Option<MeshHit<'_>> {
+        let mut closest: Option<MeshHit> = None;
+        self.bvh.closest(ray, |index| {
+            let hit = self.meshes[index].intersect(ray)?;
+            if !matches!(closest, Some(closest) if closest.distance <= hit.distance) {
+                closest = Some(hit);
+            }
+            Some(hit.distance)
+        });
+        closest
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,41 +219,387 @@ pub struct Sphere {
     pub radius: f32,
 }
 
+// Box defined by its center, half of its size along each axis and its orientation
+//  given as three orthonormal axes
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+    pub center: Vector3,
+    pub half_size: Vector3,
+    pub axes: [Vector3; 3],
+}
+
+impl Cuboid {
+    pub fn axis_aligned(min: Vector3, max: Vector3) -> Self {
+        Self {
+            center: (min + max) * 0.5,
+            half_size: (max - min) * 0.5,
+            axes: [
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ],
+        }
+    }
+
+    // Rotation is given in degrees around the x, y and z axes
+    pub fn oriented(center: Vector3, half_size: Vector3, rotation: Vector3) -> Self {
+        Self {
+            center,
+            half_size,
+            axes: [
+                Vector3::new(1.0, 0.0, 0.0).rotate(rotation),
+                Vector3::new(0.0, 1.0, 0.0).rotate(rotation),
+                Vector3::new(0.0, 0.0, 1.0).rotate(rotation),
+            ],
+        }
+    }
+
+    fn half_sizes(&self) -> [f32; 3] {
+        [self.half_size.x, self.half_size.y, self.half_size.z]
+    }
+
+    // Position of the point in the box space, scaled so that the faces are at -1 and 1.
+    //  Along axes where the box is flat the point is always on a face
+    fn local_point(&self, point: Vector3) -> [f32; 3] {
+        let offset = point - self.center;
+        let half_sizes = self.half_sizes();
+        [0, 1, 2].map(|i| {
+            let distance = offset.dot(&self.axes[i]);
+            if half_sizes[i] > 0.0 {
+                distance / half_sizes[i]
+            } else {
+                distance.signum()
+            }
+        })
+    }
+
+    // The face that was hit is the one on the axis where the point is furthest from the center
+    fn face(&self, local_point: [f32; 3]) -> (usize, f32) {
+        let axis = (0..3)
+            .max_by(|a, b| local_point[*a].abs().total_cmp(&local_point[*b].abs()))
+            .unwrap();
+        (axis, local_point[axis].signum())
+    }
+}
+
+// Cylinder standing on its base, going up along the (normalized) axis
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    pub base: Vector3,
+    pub axis: Vector3,
+    pub radius: f32,
+    pub height: f32,
+    pub capped: bool,
+}
+
+// Cone standing on its base, going up along the (normalized) axis. A top radius
+//  other than zero cuts the tip off, like a lamp shade
+#[derive(Debug, Clone)]
+pub struct Cone {
+    pub base: Vector3,
+    pub axis: Vector3,
+    pub radius: f32,
+    pub top_radius: f32,
+    // Always positive, the side would have no slope otherwise
+    height: f32,
+    pub capped: bool,
+}
+
+// Flat circle, visible from both sides
+#[derive(Debug, Clone)]
+pub struct Disk {
+    pub center: Vector3,
+    pub normal: Vector3,
+    pub radius: f32,
+}
+
+// Flat ring, visible from both sides
 #[derive(Debug, Clone)]
-pub struct Intersection {
+pub struct Annulus {
+    pub center: Vector3,
+    pub normal: Vector3,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+}
+
+// Ring around the (normalized) axis. The major radius goes from the center to the
+//  middle of the tube, the minor radius is the radius of the tube
+#[derive(Debug, Clone)]
+pub struct Torus {
+    pub center: Vector3,
+    pub axis: Vector3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+// Position of a point in cylindrical coordinates around an axis
+struct AxisPoint {
+    // Angle around the axis, from 0 to 1
+    angle: f32,
+    // Distance from the axis and the direction pointing away from it
+    distance: f32,
+    radial: Vector3,
+    height: f32,
+}
+
+impl AxisPoint {
+    fn new(point: Vector3, base: Vector3, axis: Vector3) -> Self {
+        let offset = point - base;
+        let height = offset.dot(&axis);
+        let radial = offset - axis * height;
+        let distance = radial.length();
+
+        let (x_axis, y_axis) = axis.basis();
+        let phi = radial.dot(&y_axis).atan2(radial.dot(&x_axis)); // -PI..PI
+
+        Self {
+            angle: (1.0 + (phi / std::f32::consts::PI)) * 0.5,
+            distance,
+            radial: if distance > 1e-6 {
+                radial * (1.0 / distance)
+            } else {
+                x_axis
+            },
+            height,
+        }
+    }
+
+    // Direction in which the angle grows
+    fn tangent(&self, axis: Vector3) -> Vector3 {
+        axis.cross(&self.radial)
+    }
+}
+
+// Distance along the ray to the plane through the point, for both sides of it
+fn plane_distance(ray: &Ray, point: Vector3, normal: Vector3) -> Option<f32> {
+    let denom = normal.dot(&ray.direction);
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+
+    let distance = (point - ray.origin).dot(&normal) / denom;
+    if distance < 0.0 {
+        return None;
+    }
+    Some(distance)
+}
+
+// Closest hit that is in front of the ray
+fn closest(distances: impl IntoIterator<Item = Option<f32>>) -> Option<f32> {
+    distances
+        .into_iter()
+        .flatten()
+        .filter(|d| *d >= 0.0)
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Intersection<'a> {
     pub distance: f32,
-    pub object: Object,
+    pub object: &'a Object,
+    pub material: &'a Material,
+    pub hit_point: Vector3,
+    // Geometric normal, always facing against the ray
+    pub normal: Vector3,
+    // Normal perturbed by the material, used for lighting and reflections
+    pub shading_normal: Vector3,
+    pub texture_coords: Vector2,
+    pub tangent: Vector3,
+    pub bitangent: Vector3,
+    // Whether the ray hit the outside of the surface
+    pub front_face: bool,
+}
+
+// Local properties of a surface at a given point
+#[derive(Debug, Clone, Copy)]
+pub struct Surface {
+    pub normal: Vector3,
+    pub texture_coords: Vector2,
+    pub tangent: Vector3,
 }
 
 pub trait Intersectable {
     fn intersect_distance(&self, ray: &Ray) -> Option<f32>;
+
+    // None if the shape is infinite
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+// Single shape, whose surface can be looked up from a point on it
+pub trait Shape: Intersectable + Debug {
     fn surface_normal(&self, hit_point: Vector3) -> Vector3;
     fn texture_coords(&self, hit_point: Vector3) -> Vector2;
+    // Direction in which the u texture coordinate grows, used to build the tangent frame
+    fn tangent(&self, hit_point: Vector3) -> Vector3;
+
+    // Every span of the ray that is inside the shape, sorted by distance. By default it
+    //  walks from surface to surface, closed shapes can override it with exact spans
+    fn intersect_intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        march_intervals(ray, |ray| {
+            let distance = self.intersect_distance(ray)?;
+            let hit_point = ray.origin + (ray.direction * distance);
+            let entering = self.surface_normal(hit_point).dot(&ray.direction) < 0.0;
+            Some((distance, entering))
+        })
+    }
+
+    // Computes all the surface properties at once, shapes can override it
+    //  to share work between them
+    fn surface(&self, hit_point: Vector3) -> Surface {
+        Surface {
+            normal: self.surface_normal(hit_point),
+            texture_coords: self.texture_coords(hit_point),
+            tangent: self.tangent(hit_point),
+        }
+    }
+}
+
+// The shape of a mesh that a ray landed on
+#[derive(Debug, Clone, Copy)]
+pub struct Part<'a> {
+    pub shape: &'a dyn Shape,
+    // Seen from the inside, like the surface a difference carves out
+    pub inverted: bool,
+}
+
+impl<'a> Part<'a> {
+    pub fn new(shape: &'a dyn Shape) -> Self {
+        Self {
+            shape,
+            inverted: false,
+        }
+    }
+
+    pub fn inverted(self) -> Self {
+        Self {
+            inverted: !self.inverted,
+            ..self
+        }
+    }
+
+    pub fn surface(&self, hit_point: Vector3) -> Surface {
+        let surface = self.shape.surface(hit_point);
+        if self.inverted {
+            Surface {
+                normal: -surface.normal,
+                ..surface
+            }
+        } else {
+            surface
+        }
+    }
+}
+
+// Where a ray hits a mesh, in the space of the mesh. Keeps the shape that was hit so
+//  meshes made out of other meshes don't have to look for it again
+#[derive(Debug, Clone, Copy)]
+pub struct MeshHit<'a> {
+    pub distance: f32,
+    pub part: Part<'a>,
+}
+
+impl MeshHit<'_> {
+    pub fn surface(&self, ray: &Ray) -> Surface {
+        self.part
+            .surface(ray.origin + (ray.direction * self.distance))
+    }
 }
 
 impl Intersectable for Mesh {
     fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
-        match &self {
+        match self {
             Mesh::Sphere(s) => s.intersect_distance(ray),
             Mesh::Plane(p) => p.intersect_distance(ray),
+            Mesh::Box(b) => b.intersect_distance(ray),
+            Mesh::Cylinder(c) => c.intersect_distance(ray),
+            Mesh::Cone(c) => c.intersect_distance(ray),
+            Mesh::Disk(d) => d.intersect_distance(ray),
+            Mesh::Annulus(a) => a.intersect_distance(ray),
+            Mesh::Torus(t) => t.intersect_distance(ray),
+            Mesh::Group(group) => group.closest_hit(ray).map(|(_, distance)| distance),
+            Mesh::Csg(csg) => csg.intersect_distance(ray),
+            Mesh::Sdf(s) => s.intersect_distance(ray),
+            Mesh::Heightfield(h) => h.intersect_distance(ray),
         }
     }
 
-    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
-        match &self {
-            Mesh::Sphere(s) => s.surface_normal(hit_point),
-            Mesh::Plane(p) => p.surface_normal(hit_point),
+    fn bounds(&self) -> Option<Aabb> {
+        match self {
+            Mesh::Sphere(s) => s.bounds(),
+            Mesh::Plane(p) => p.bounds(),
+            Mesh::Box(b) => b.bounds(),
+            Mesh::Cylinder(c) => c.bounds(),
+            Mesh::Cone(c) => c.bounds(),
+            Mesh::Disk(d) => d.bounds(),
+            Mesh::Annulus(a) => a.bounds(),
+            Mesh::Torus(t) => t.bounds(),
+            Mesh::Group(group) => group.bounds,
+            Mesh::Csg(csg) => csg.bounds(),
+            Mesh::Sdf(s) => s.bounds(),
+            Mesh::Heightfield(h) => h.bounds(),
         }
     }
+}
 
-    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
-        match &self {
-            Mesh::Sphere(s) => s.texture_coords(hit_point),
-            Mesh::Plane(p) => p.texture_coords(hit_point),
+impl Mesh {
+    // Closest hit, along with the shape that was hit
+    pub fn intersect(&self, ray: &Ray) -> Option<MeshHit<'_>> {
+        match self {
+            Mesh::Sphere(s) => shape_hit(s, ray),
+            Mesh::Plane(p) => shape_hit(p, ray),
+            Mesh::Box(b) => shape_hit(b, ray),
+            Mesh::Cylinder(c) => shape_hit(c, ray),
+            Mesh::Cone(c) => shape_hit(c, ray),
+            Mesh::Disk(d) => shape_hit(d, ray),
+            Mesh::Annulus(a) => shape_hit(a, ray),
+            Mesh::Torus(t) => shape_hit(t, ray),
+            Mesh::Group(group) => group.intersect(ray),
+            Mesh::Csg(csg) => csg.intersect(ray),
+            Mesh::Sdf(s) => shape_hit(s, ray),
+            Mesh::Heightfield(h) => shape_hit(h, ray),
+        }
+    }
+
+    // Every span of the ray that is inside the mesh, sorted by distance
+    pub fn intersect_intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match self {
+            Mesh::Sphere(s) => shape_intervals(s, ray),
+            Mesh::Plane(p) => shape_intervals(p, ray),
+            Mesh::Box(b) => shape_intervals(b, ray),
+            Mesh::Cylinder(c) => shape_intervals(c, ray),
+            Mesh::Cone(c) => shape_intervals(c, ray),
+            Mesh::Disk(d) => shape_intervals(d, ray),
+            Mesh::Annulus(a) => shape_intervals(a, ray),
+            Mesh::Torus(t) => shape_intervals(t, ray),
+            Mesh::Group(group) => group
+                .meshes
+                .iter()
+                .map(|mesh| mesh.intersect_intervals(ray))
+                .fold(Vec::new(), |a, b| combine(CsgOperation::Union, &a, &b)),
+            Mesh::Csg(csg) => csg.intersect_intervals(ray),
+            Mesh::Sdf(s) => shape_intervals(s, ray),
+            Mesh::Heightfield(h) => shape_intervals(h, ray),
         }
     }
 }
 
+fn shape_hit<'a>(shape: &'a impl Shape, ray: &Ray) -> Option<MeshHit<'a>> {
+    shape.intersect_distance(ray).map(|distance| MeshHit {
+        distance,
+        part: Part::new(shape),
+    })
+}
+
+// The spans of the shape, with the shape attached to their ends
+fn shape_intervals<'a>(shape: &'a impl Shape, ray: &Ray) -> Vec<Interval<'a>> {
+    let part = Part::new(shape);
+    shape
+        .intersect_intervals(ray)
+        .into_iter()
+        .map(|interval| interval.with_part(part))
+        .collect()
+}
+
 impl Intersectable for Plane {
     fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
         // https://www.scratchapixel.com/lessons/3d-basic-rendering/minimal-ray-tracer-rendering-simple-shapes/ray-plane-and-ray-disk-intersection
@@ -88,34 +612,52 @@ impl Intersectable for Plane {
             return None;
         }
 
-        let num = (self.origin - ray.origin).dot(&normal);
+        let num = (self.origin - ray.origin).dot(normal);
         let distance = num / denom;
 
+        // The plane is behind the ray
+        if distance < 0.0 {
+            return None;
+        }
+
         Some(distance)
     }
 
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+impl Shape for Plane {
     fn surface_normal(&self, _hit_point: Vector3) -> Vector3 {
         -self.normal
     }
 
     fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
-        // Create a 2d space
-        let x_axis = if self.normal.z != 0.0 {
-            self.normal.cross(&Vector3::new(0.0, 0.0, 1.0))
-        } else {
-            self.normal.cross(&Vector3::new(1.0, 1.0, 0.0))
-        };
-        let y_axis = self.normal.cross(&x_axis);
+        let (x_axis, y_axis) = self.axes();
 
         let hit_vector = hit_point - self.origin;
 
         // Calculate the texture coordinates by computing the distance from the hit point
         Vector2::new(hit_vector.dot(&x_axis), hit_vector.dot(&y_axis))
     }
+
+    fn tangent(&self, _hit_point: Vector3) -> Vector3 {
+        self.axes().0
+    }
 }
 
-impl Intersectable for Sphere {
-    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+impl Plane {
+    // Create a 2d space on the plane, it works for any facing. It is built around the
+    //  side that can be hit, so u, v and that side's normal make a right handed frame
+    fn axes(&self) -> (Vector3, Vector3) {
+        (-self.normal).normalize().basis()
+    }
+}
+
+impl Sphere {
+    // Distances where the ray goes in and out of the sphere, even behind it
+    fn crossings(&self, ray: &Ray) -> Option<(f32, f32)> {
         // Trigonometry yay!
         let line = self.center - ray.origin;
         let adj = line.dot(&ray.direction);
@@ -132,18 +674,42 @@ impl Intersectable for Sphere {
         let thickness = (radius2 - distance2).sqrt();
 
         // Both intersection points
-        let intersection_in = adj - thickness;
-        let intersection_out = adj + thickness;
+        Some((adj - thickness, adj + thickness))
+    }
+}
+
+impl Intersectable for Sphere {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let (intersection_in, intersection_out) = self.crossings(ray)?;
 
-        // TODO: Is this necessary?
-        if intersection_in < 0.0 && intersection_out < 0.0 {
+        // The sphere is behind the ray
+        if intersection_out < 0.0 {
             return None;
         }
 
-        let distance = intersection_in.min(intersection_out);
+        // If the ray starts inside the sphere only the exit point is in front of it
+        let distance = if intersection_in < 0.0 {
+            intersection_out
+        } else {
+            intersection_in
+        };
         Some(distance)
     }
 
+    fn bounds(&self) -> Option<Aabb> {
+        let radius = Vector3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - radius, self.center + radius))
+    }
+}
+
+impl Shape for Sphere {
+    fn intersect_intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        self.crossings(ray)
+            .map(|(enter, exit)| Interval::new(enter, exit))
+            .into_iter()
+            .collect()
+    }
+
     fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
         (hit_point - self.center).normalize()
     }
@@ -161,4 +727,699 @@ impl Intersectable for Sphere {
             theta / std::f32::consts::PI,
         )
     }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        let hit_vector = hit_point - self.center;
+
+        // Derivative of the hit point with respect to phi
+        let tangent = Vector3::new(-hit_vector.z, 0.0, hit_vector.x);
+
+        // At the poles phi is undefined, any horizontal direction will do
+        if tangent.norm() < 1e-12 {
+            Vector3::new(1.0, 0.0, 0.0)
+        } else {
+            tangent.normalize()
+        }
+    }
+}
+
+impl Intersectable for Cuboid {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        // Slab method, done in the space of the box so it can be rotated
+        let offset = ray.origin - self.center;
+        let half_sizes = self.half_sizes();
+
+        let mut near = f32::NEG_INFINITY;
+        let mut far = f32::INFINITY;
+
+        for (axis, half_size) in self.axes.iter().zip(half_sizes) {
+            let origin = offset.dot(axis);
+            let direction = ray.direction.dot(axis);
+
+            // Parallel to the slab, it can only hit if it starts inside it
+            if direction.abs() < 1e-8 {
+                if origin.abs() > half_size {
+                    return None;
+                }
+                continue;
+            }
+
+            let t1 = (-half_size - origin) / direction;
+            let t2 = (half_size - origin) / direction;
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+
+        if far < near || far < 0.0 {
+            return None;
+        }
+
+        // If the ray starts inside the box only the exit point is in front of it
+        if near < 0.0 {
+            Some(far)
+        } else {
+            Some(near)
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let half_sizes = self.half_sizes();
+        let extent = (0..3)
+            .map(|i| self.axes[i] * half_sizes[i])
+            .map(|v| Vector3::new(v.x.abs(), v.y.abs(), v.z.abs()))
+            .fold(Vector3::zero(), |a, b| a + b);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl Shape for Cuboid {
+    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
+        let (axis, sign) = self.face(self.local_point(hit_point));
+        self.axes[axis] * sign
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        let local_point = self.local_point(hit_point);
+        let (axis, sign) = self.face(local_point);
+        let [x, y, z] = local_point;
+
+        // Every face is unwrapped to the whole texture, as seen from the outside with y up
+        let (u, v) = match axis {
+            0 => (-sign * z, -y),
+            1 => (x, sign * z),
+            _ => (sign * x, -y),
+        };
+
+        Vector2::new((1.0 + u) * 0.5, (1.0 + v) * 0.5)
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        let (axis, sign) = self.face(self.local_point(hit_point));
+        match axis {
+            0 => self.axes[2] * -sign,
+            1 => self.axes[0],
+            _ => self.axes[0] * sign,
+        }
+    }
+}
+
+impl Cylinder {
+    // Whether the point lies on one of the caps rather than on the side
+    fn is_on_cap(&self, point: &AxisPoint) -> bool {
+        let side_error = (point.distance - self.radius).abs();
+        let cap_error = point.height.abs().min((point.height - self.height).abs());
+        self.capped && cap_error < side_error
+    }
+}
+
+impl Intersectable for Cylinder {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let offset = ray.origin - self.base;
+        let origin_height = offset.dot(&self.axis);
+        let direction_height = ray.direction.dot(&self.axis);
+
+        // Project everything onto the plane perpendicular to the axis
+        let origin = offset - self.axis * origin_height;
+        let direction = ray.direction - self.axis * direction_height;
+
+        let in_height = |distance: f32| {
+            let height = origin_height + distance * direction_height;
+            (0.0..=self.height).contains(&height)
+        };
+        let in_radius = |distance: f32| {
+            let point = ray.origin + ray.direction * distance;
+            let offset = point - self.base;
+            (offset - self.axis * offset.dot(&self.axis)).norm() <= self.radius.powi(2)
+        };
+
+        let side = solve_quadratic(
+            direction.dot(&direction),
+            2.0 * origin.dot(&direction),
+            origin.dot(&origin) - self.radius.powi(2),
+        );
+        let (side_in, side_out) = match side {
+            Some((near, far)) => (Some(near), Some(far)),
+            None => (None, None),
+        };
+
+        let caps = if self.capped {
+            [
+                plane_distance(ray, self.base, self.axis),
+                plane_distance(ray, self.base + self.axis * self.height, self.axis),
+            ]
+        } else {
+            [None, None]
+        };
+
+        closest([
+            side_in.filter(|d| in_height(*d)),
+            side_out.filter(|d| in_height(*d)),
+            caps[0].filter(|d| in_radius(*d)),
+            caps[1].filter(|d| in_radius(*d)),
+        ])
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let top = self.base + self.axis * self.height;
+        Some(
+            Aabb::disk(self.base, self.axis, self.radius).union(&Aabb::disk(
+                top,
+                self.axis,
+                self.radius,
+            )),
+        )
+    }
+}
+
+impl Shape for Cylinder {
+    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
+        let point = AxisPoint::new(hit_point, self.base, self.axis);
+        if !self.is_on_cap(&point) {
+            point.radial
+        } else if point.height < self.height * 0.5 {
+            -self.axis
+        } else {
+            self.axis
+        }
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        let point = AxisPoint::new(hit_point, self.base, self.axis);
+        if self.is_on_cap(&point) {
+            // The caps are mapped like a disk
+            Vector2::new(point.angle, point.distance / self.radius)
+        } else {
+            Vector2::new(point.angle, 1.0 - point.height / self.height)
+        }
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        AxisPoint::new(hit_point, self.base, self.axis).tangent(self.axis)
+    }
+}
+
+impl Cone {
+    // None if the height isn't positive
+    pub fn new(
+        base: Vector3,
+        axis: Vector3,
+        radius: f32,
+        top_radius: f32,
+        height: f32,
+        capped: bool,
+    ) -> Option<Self> {
+        if height <= 0.0 || height.is_nan() {
+            return None;
+        }
+        Some(Self {
+            base,
+            axis,
+            radius,
+            top_radius,
+            height,
+            capped,
+        })
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    // How much the radius shrinks per unit of height
+    fn slope(&self) -> f32 {
+        (self.radius - self.top_radius) / self.height
+    }
+
+    fn radius_at(&self, height: f32) -> f32 {
+        self.radius - self.slope() * height
+    }
+
+    // Whether the point lies on one of the caps rather than on the side
+    fn is_on_cap(&self, point: &AxisPoint) -> bool {
+        let side_error = (point.distance - self.radius_at(point.height)).abs();
+        let cap_error = point.height.abs().min((point.height - self.height).abs());
+        self.capped && cap_error < side_error
+    }
+}
+
+impl Intersectable for Cone {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let offset = ray.origin - self.base;
+        let origin_height = offset.dot(&self.axis);
+        let direction_height = ray.direction.dot(&self.axis);
+
+        // Project everything onto the plane perpendicular to the axis
+        let origin = offset - self.axis * origin_height;
+        let direction = ray.direction - self.axis * direction_height;
+
+        // The radius along the ray changes linearly with the distance
+        let slope = self.slope();
+        let origin_radius = self.radius - slope * origin_height;
+        let direction_radius = -slope * direction_height;
+
+        let in_height = |distance: f32| {
+            let height = origin_height + distance * direction_height;
+            (0.0..=self.height).contains(&height)
+        };
+        let in_radius = |distance: f32, radius: f32| {
+            let point = ray.origin + ray.direction * distance;
+            let offset = point - self.base;
+            (offset - self.axis * offset.dot(&self.axis)).norm() <= radius.powi(2)
+        };
+
+        let side = solve_quadratic(
+            direction.dot(&direction) - direction_radius.powi(2),
+            2.0 * (origin.dot(&direction) - origin_radius * direction_radius),
+            origin.dot(&origin) - origin_radius.powi(2),
+        );
+        let (side_in, side_out) = match side {
+            Some((near, far)) => (Some(near), Some(far)),
+            None => (None, None),
+        };
+
+        let caps = if self.capped {
+            [
+                plane_distance(ray, self.base, self.axis),
+                plane_distance(ray, self.base + self.axis * self.height, self.axis),
+            ]
+        } else {
+            [None, None]
+        };
+
+        closest([
+            side_in.filter(|d| in_height(*d)),
+            side_out.filter(|d| in_height(*d)),
+            caps[0].filter(|d| in_radius(*d, self.radius)),
+            caps[1].filter(|d| in_radius(*d, self.top_radius)),
+        ])
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let top = self.base + self.axis * self.height;
+        Some(
+            Aabb::disk(self.base, self.axis, self.radius).union(&Aabb::disk(
+                top,
+                self.axis,
+                self.top_radius,
+            )),
+        )
+    }
+}
+
+impl Shape for Cone {
+    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
+        let point = AxisPoint::new(hit_point, self.base, self.axis);
+        if !self.is_on_cap(&point) {
+            // The side leans towards the axis, so the normal leans up by the slope
+            (point.radial + self.axis * self.slope()).normalize()
+        } else if point.height < self.height * 0.5 {
+            -self.axis
+        } else {
+            self.axis
+        }
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        let point = AxisPoint::new(hit_point, self.base, self.axis);
+        if self.is_on_cap(&point) {
+            // The caps are mapped like a disk
+            let radius = if point.height < self.height * 0.5 {
+                self.radius
+            } else {
+                self.top_radius
+            };
+            Vector2::new(point.angle, point.distance / radius)
+        } else {
+            Vector2::new(point.angle, 1.0 - point.height / self.height)
+        }
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        AxisPoint::new(hit_point, self.base, self.axis).tangent(self.axis)
+    }
+}
+
+impl Intersectable for Disk {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let distance = plane_distance(ray, self.center, self.normal)?;
+        let hit_point = ray.origin + ray.direction * distance;
+
+        if (hit_point - self.center).norm() > self.radius.powi(2) {
+            return None;
+        }
+        Some(distance)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::disk(self.center, self.normal, self.radius))
+    }
+}
+
+impl Shape for Disk {
+    fn surface_normal(&self, _hit_point: Vector3) -> Vector3 {
+        self.normal
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        let point = AxisPoint::new(hit_point, self.center, self.normal);
+        Vector2::new(point.angle, point.distance / self.radius)
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        AxisPoint::new(hit_point, self.center, self.normal).tangent(self.normal)
+    }
+}
+
+impl Intersectable for Annulus {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let distance = plane_distance(ray, self.center, self.normal)?;
+        let hit_point = ray.origin + ray.direction * distance;
+
+        let distance2 = (hit_point - self.center).norm();
+        if distance2 > self.outer_radius.powi(2) || distance2 < self.inner_radius.powi(2) {
+            return None;
+        }
+        Some(distance)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::disk(self.center, self.normal, self.outer_radius))
+    }
+}
+
+impl Shape for Annulus {
+    fn surface_normal(&self, _hit_point: Vector3) -> Vector3 {
+        self.normal
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        let point = AxisPoint::new(hit_point, self.center, self.normal);
+        Vector2::new(
+            point.angle,
+            (point.distance - self.inner_radius) / (self.outer_radius - self.inner_radius),
+        )
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        AxisPoint::new(hit_point, self.center, self.normal).tangent(self.normal)
+    }
+}
+
+impl Intersectable for Torus {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        // Skip rays that miss the bounding sphere, and start the others where they enter it.
+        //  The closer the origin is to the torus the better the precision of the solver
+        let bounds = Sphere {
+            center: self.center,
+            radius: self.major_radius + self.minor_radius,
+        };
+        let start = bounds.intersect_distance(ray)?;
+        let start = if (ray.origin - self.center).norm() < bounds.radius.powi(2) {
+            0.0
+        } else {
+            start
+        };
+
+        // Work in f64 in the space of the torus, with the axis as z
+        let (x_axis, y_axis) = self.axis.basis();
+        let offset = ray.origin + ray.direction * start - self.center;
+        let to_local = |v: Vector3| {
+            [
+                v.dot(&x_axis) as f64,
+                v.dot(&y_axis) as f64,
+                v.dot(&self.axis) as f64,
+            ]
+        };
+        let [ox, oy, oz] = to_local(offset);
+        let [dx, dy, dz] = to_local(ray.direction);
+
+        let major2 = (self.major_radius as f64).powi(2);
+        let minor2 = (self.minor_radius as f64).powi(2);
+
+        // Substitute the ray into (|p|^2 + R^2 - r^2)^2 = 4R^2 (p.x^2 + p.y^2)
+        let direction2 = dx * dx + dy * dy + dz * dz;
+        let projection = ox * dx + oy * dy + oz * dz;
+        let g = ox * ox + oy * oy + oz * oz + major2 - minor2;
+
+        let roots = solve_quartic(
+            direction2 * direction2,
+            4.0 * direction2 * projection,
+            4.0 * projection * projection + 2.0 * direction2 * g
+                - 4.0 * major2 * (dx * dx + dy * dy),
+            4.0 * projection * g - 8.0 * major2 * (ox * dx + oy * dy),
+            g * g - 4.0 * major2 * (ox * ox + oy * oy),
+        );
+
+        closest(roots.map(|root| root.map(|root| root as f32 + start)))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // The outer ring, thickened by the tube along the axis
+        let ring = Aabb::disk(
+            self.center,
+            self.axis,
+            self.major_radius + self.minor_radius,
+        );
+        let thickness = self.axis * self.minor_radius;
+        Some(
+            Aabb::new(ring.min + thickness, ring.max + thickness)
+                .union(&Aabb::new(ring.min - thickness, ring.max - thickness)),
+        )
+    }
+}
+
+impl Shape for Torus {
+    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
+        // Away from the closest point in the circle that goes through the middle of the tube
+        let point = AxisPoint::new(hit_point, self.center, self.axis);
+        let ring_point = self.center + point.radial * self.major_radius;
+        (hit_point - ring_point).normalize()
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        // u goes around the axis and v around the tube
+        let point = AxisPoint::new(hit_point, self.center, self.axis);
+        let theta = point.height.atan2(point.distance - self.major_radius); // -PI..PI
+
+        Vector2::new(point.angle, (1.0 + (theta / std::f32::consts::PI)) * 0.5)
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        AxisPoint::new(hit_point, self.center, self.axis).tangent(self.axis)
+    }
+}
+
+#[test]
+fn test_normal_mapped_plane_facing_z() {
+    use crate::material::{Color, Coloring, Material, SurfaceKind, Texture};
+
+    let mut assets = Assets::new();
+    // Red leans towards +u and green towards +v, which run along -y and +x here
+    let normal_map = assets.add_texture(Texture::new(image::DynamicImage::ImageRgb8(
+        image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 128])),
+    )));
+    // Heights going up along u
+    let bump_map = assets.add_texture(Texture::new(image::DynamicImage::ImageRgb8(
+        image::RgbImage::from_fn(4, 1, |x, _| image::Rgb([(x * 85) as u8; 3])),
+    )));
+    let white = || {
+        Material::new(
+            Coloring::Color(Color::new(255, 255, 255)),
+            1.0,
+            SurfaceKind::Diffuse,
+        )
+    };
+    let normal_mapped = assets.add_material(white().with_normal_map(normal_map));
+    let bumped = assets.add_material(white().with_bump_map(bump_map, 3.0));
+
+    let plane = |material| {
+        Object::new(
+            material,
+            Mesh::Plane(Plane {
+                normal: Vector3::new(0.0, 0.0, -1.0),
+                origin: Vector3::new(0.0, 0.0, -5.0),
+            }),
+        )
+    };
+    let ray = Ray {
+        origin: Vector3::new(0.5, 0.5, 0.0),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+
+    let (normal_mapped, bumped) = (plane(normal_mapped), plane(bumped));
+    let hit = normal_mapped.intersect(&ray, &assets).unwrap();
+    assert!((hit.tangent - Vector3::new(0.0, -1.0, 0.0)).length() < 1e-5);
+    assert!((hit.bitangent - Vector3::new(1.0, 0.0, 0.0)).length() < 1e-5);
+    let expected = Vector3::new(-1.0, -1.0, 128.0 / 255.0 * 2.0 - 1.0).normalize();
+    assert!((hit.shading_normal - expected).length() < 1e-4);
+
+    // The normal leans away from the slope, towards -u
+    let hit = bumped.intersect(&ray, &assets).unwrap();
+    let expected = Vector3::new(0.0, 1.0, 1.0).normalize();
+    assert!((hit.shading_normal - expected).length() < 1e-4);
+}
+
+#[test]
+fn test_box_face_normals() {
+    let cube = Cuboid::axis_aligned(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0));
+    let offset = Vector3::new(0.3, 0.2, 0.1);
+    for axis in [
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, 0.0, 1.0),
+    ] {
+        for normal in [axis, -axis] {
+            // Off the middle of the face, but closer to it than to the others
+            let point = normal + offset - normal * normal.dot(&offset);
+            assert!((cube.surface_normal(point).dot(&normal) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    // A box with no height is a square seen from both sides
+    let flat = Cuboid::axis_aligned(Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 0.0, 1.0));
+    let ray = Ray {
+        origin: Vector3::new(0.5, 2.0, -0.5),
+        direction: Vector3::new(0.0, -1.0, 0.0),
+        time: 0.0,
+    };
+    let distance = flat.intersect_distance(&ray).unwrap();
+    assert!((distance - 2.0).abs() < 1e-6);
+
+    let surface = flat.surface(ray.origin + ray.direction * distance);
+    assert!((surface.normal.y.abs() - 1.0).abs() < 1e-6);
+    assert!(surface.texture_coords.x.is_finite() && surface.texture_coords.y.is_finite());
+    assert!(surface.tangent.x.is_finite() && surface.tangent.y.is_finite());
+}
+
+#[test]
+fn test_cylinder_and_cone_caps() {
+    let down = |x: f32| Ray {
+        origin: Vector3::new(x, 5.0, 0.0),
+        direction: Vector3::new(0.0, -1.0, 0.0),
+        time: 0.0,
+    };
+    let up = Vector3::new(0.0, 1.0, 0.0);
+
+    let mut cylinder = Cylinder {
+        base: Vector3::zero(),
+        axis: up,
+        radius: 1.0,
+        height: 2.0,
+        capped: true,
+    };
+    let distance = cylinder.intersect_distance(&down(0.5)).unwrap();
+    assert!((distance - 3.0).abs() < 1e-5);
+    assert!(
+        (cylinder
+            .surface_normal(Vector3::new(0.5, 2.0, 0.0))
+            .dot(&up)
+            - 1.0)
+            .abs()
+            < 1e-6
+    );
+    assert!(
+        (cylinder
+            .surface_normal(Vector3::new(0.5, 0.0, 0.0))
+            .dot(&up)
+            + 1.0)
+            .abs()
+            < 1e-6
+    );
+
+    // Without caps a ray along the axis goes right through
+    cylinder.capped = false;
+    assert!(cylinder.intersect_distance(&down(0.5)).is_none());
+
+    let cone = Cone::new(Vector3::zero(), up, 1.0, 0.5, 2.0, true).unwrap();
+    let distance = cone.intersect_distance(&down(0.25)).unwrap();
+    assert!((distance - 3.0).abs() < 1e-5);
+    assert!((cone.surface_normal(Vector3::new(0.25, 2.0, 0.0)).dot(&up) - 1.0).abs() < 1e-6);
+
+    // Past the top cap the ray lands on the side, halfway up
+    let distance = cone.intersect_distance(&down(0.75)).unwrap();
+    assert!((distance - 4.0).abs() < 1e-4);
+    let normal = cone.surface_normal(Vector3::new(0.75, 1.0, 0.0));
+    assert!(normal.x > 0.9 && normal.y > 0.0);
+
+    // A flat cone has no slope to give its side
+    assert!(Cone::new(Vector3::zero(), up, 1.0, 0.5, 0.0, true).is_none());
+    assert!(Cone::new(Vector3::zero(), up, 1.0, 0.5, -1.0, true).is_none());
+}
+
+#[test]
+fn test_motion_turns_objects_in_place() {
+    use crate::{
+        material::{Color, Coloring, SurfaceKind},
+        motion::{Keyframe, Motion, Placement},
+        transform::Transform,
+    };
+
+    let spin = Motion::linear(
+        Keyframe::new(0.0, Placement::default()),
+        Keyframe::new(
+            1.0,
+            Placement::new(
+                Vector3::zero(),
+                Vector3::new(0.0, 90.0, 0.0),
+                Vector3::new(1.0, 1.0, 1.0),
+            ),
+        ),
+    );
+    let material = Assets::new().add_material(Material::new(
+        Coloring::Color(Color::new(255, 255, 255)),
+        1.0,
+        SurfaceKind::Diffuse,
+    ));
+    let object = Object::new(
+        material,
+        Mesh::Sphere(Sphere {
+            center: Vector3::zero(),
+            radius: 1.0,
+        }),
+    )
+    .with_transform(Transform::identity().translate(Vector3::new(5.0, 0.0, 0.0)))
+    .with_motion(spin);
+
+    // The center stays put while the object turns around it
+    let center = object.transform_at(1.0).point_to_world(Vector3::zero());
+    assert!((center - Vector3::new(5.0, 0.0, 0.0)).length() < 1e-5);
+    let side = object
+        .transform_at(1.0)
+        .point_to_world(Vector3::new(1.0, 0.0, 0.0));
+    assert!((side - Vector3::new(5.0, 0.0, -1.0)).length() < 1e-5);
+}
+
+#[test]
+fn test_torus_hits() {
+    let torus = Torus {
+        center: Vector3::zero(),
+        axis: Vector3::new(0.0, 1.0, 0.0),
+        major_radius: 2.0,
+        minor_radius: 0.5,
+    };
+    // From far away, where the precision of the solver matters the most
+    let towards = |x: f32, y: f32| Ray {
+        origin: Vector3::new(x, y, 50.0),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+
+    // Through the tube, along the ring
+    let distance = torus.intersect_distance(&towards(2.0, 0.0)).unwrap();
+    assert!((distance - 48.5).abs() < 1e-3);
+
+    // Down the axis, through the hole
+    let down = Ray {
+        origin: Vector3::new(0.0, 5.0, 0.0),
+        direction: Vector3::new(0.0, -1.0, 0.0),
+        time: 0.0,
+    };
+    assert!(torus.intersect_distance(&down).is_none());
+
+    // Grazing the outer edge, just inside and just outside of it
+    let inside = torus.intersect_distance(&towards(2.49, 0.0)).unwrap();
+    let expected = 50.0 - (2.5_f32.powi(2) - 2.49_f32.powi(2)).sqrt();
+    assert!((inside - expected).abs() < 1e-2);
+    assert!(torus.intersect_distance(&towards(2.51, 0.0)).is_none());
 }
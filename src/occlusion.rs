@@ -0,0 +1,80 @@
+use std::f32::consts::PI;
+
+use crate::{
+    material::Color,
+    vector::{Vector2, Vector3},
+};
+
+// Rays cast over the hemisphere around the normal, the share of them that hit
+//  something closer than the max distance is how occluded the point is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    pub samples: u32,
+    // Only what is this close darkens the point, so open areas stay bright
+    pub max_distance: f32,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: u32, max_distance: f32) -> Self {
+        Self {
+            samples,
+            max_distance,
+        }
+    }
+
+    // Cosine weighted, so the rays that graze the surface count less. The normal must
+    //  be normalized
+    pub fn direction(normal: Vector3, sample: Vector2) -> Vector3 {
+        let radius = sample.x.sqrt();
+        let angle = 2.0 * PI * sample.y;
+        let (first, second) = normal.basis();
+        let height = (1.0 - sample.x).max(0.0).sqrt();
+        first * (radius * angle.cos()) + second * (radius * angle.sin()) + normal * height
+    }
+}
+
+// Light that comes from everywhere, so the sides that face away from the lights
+//  aren't pitch black
+#[derive(Debug, Clone, Copy)]
+pub struct Ambient {
+    pub color: Color,
+    pub intensity: f32,
+    // Darkens the ambient light in crevices
+    pub occlusion: Option<AmbientOcclusion>,
+}
+
+impl Ambient {
+    pub fn new(color: Color, intensity: f32) -> Self {
+        Self {
+            color,
+            intensity,
+            occlusion: None,
+        }
+    }
+
+    pub fn with_occlusion(mut self, occlusion: AmbientOcclusion) -> Self {
+        self.occlusion = Some(occlusion);
+        self
+    }
+}
+
+// How the color of a hit is worked out
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Shading {
+    // Lights, shadows and reflections
+    #[default]
+    Lit,
+    // Only the ambient occlusion in shades of gray, white where nothing is near.
+    //  Materials and lights are ignored
+    AmbientOcclusion(AmbientOcclusion),
+}
+
+#[test]
+fn test_occlusion_directions_stay_in_the_hemisphere() {
+    let normal = Vector3::new(0.0, 0.0, 1.0);
+    for &(x, y) in &[(0.0, 0.0), (0.5, 0.25), (0.99, 0.75), (0.999, 0.999)] {
+        let direction = AmbientOcclusion::direction(normal, Vector2::new(x, y));
+        assert!((direction.length() - 1.0).abs() < 1e-4);
+        assert!(direction.dot(&normal) >= 0.0);
+    }
+}
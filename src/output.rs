@@ -0,0 +1,217 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{codecs::hdr::HdrEncoder, ImageResult, Rgb};
+
+use crate::vector::Vector3;
+
+// Named plane of values, one per pixel going row by row from the top left corner
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub name: String,
+    pub values: Vec<f32>,
+    pub precision: ExrPrecision,
+}
+
+// How EXR channels are stored, half is usually enough for colors but not for ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+// Radiance RGBE, shares the exponent between channels so it can't hold negative values
+pub fn write_hdr(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    pixels: &[Vector3],
+) -> ImageResult<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    let pixels: Vec<_> = pixels
+        .iter()
+        .map(|color| Rgb([color.x.max(0.0), color.y.max(0.0), color.z.max(0.0)]))
+        .collect();
+    HdrEncoder::new(writer).encode(&pixels, width as usize, height as usize)
+}
+
+// Portable float map, raw little endian floats with the rows going from bottom to top
+pub fn write_pfm(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    pixels: &[Vector3],
+) -> ImageResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    // A negative scale means little endian
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(width as usize).rev() {
+        for color in row {
+            for value in [color.x, color.y, color.z] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Uncompressed scanline OpenEXR with any number of channels. The attributes are
+//  extra string metadata for the header
+pub fn write_exr(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+    attributes: &[(String, String)],
+) -> ImageResult<()> {
+    // Readers expect the channels sorted by name
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let value_size = |precision| match precision {
+        ExrPrecision::Half => 2,
+        ExrPrecision::Float => 4,
+    };
+
+    let mut header = Vec::new();
+    // Magic number and version 2, single part scanline image
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+    let mut channel_list = Vec::new();
+    for channel in &channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        let pixel_type: i32 = match channel.precision {
+            ExrPrecision::Half => 1,
+            ExrPrecision::Float => 2,
+        };
+        channel_list.extend_from_slice(&pixel_type.to_le_bytes());
+        // Not perceptually linear, then 3 reserved bytes
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        // No subsampling in x or y
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut header_attributes: Vec<(&str, &str, Vec<u8>)> = vec![
+        ("channels", "chlist", channel_list),
+        // No compression
+        ("compression", "compression", vec![0]),
+        ("dataWindow", "box2i", window.clone()),
+        ("displayWindow", "box2i", window),
+        // Increasing y
+        ("lineOrder", "lineOrder", vec![0]),
+        ("pixelAspectRatio", "float", 1.0f32.to_le_bytes().to_vec()),
+        (
+            "screenWindowCenter",
+            "v2f",
+            [0.0f32.to_le_bytes(), 0.0f32.to_le_bytes()].concat(),
+        ),
+        ("screenWindowWidth", "float", 1.0f32.to_le_bytes().to_vec()),
+    ];
+    for (name, value) in attributes {
+        header_attributes.push((name, "string", value.as_bytes().to_vec()));
+    }
+    for (name, kind, value) in header_attributes {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(&value);
+    }
+    header.push(0);
+
+    // Every scanline is its own block, the offset table points at each of them
+    let line_size: usize = channels
+        .iter()
+        .map(|channel| width as usize * value_size(channel.precision))
+        .sum();
+    let block_size = 8 + line_size;
+    let first_block = header.len() + 8 * height as usize;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&header)?;
+    for y in 0..height as usize {
+        writer.write_all(&((first_block + y * block_size) as u64).to_le_bytes())?;
+    }
+
+    for y in 0..height as usize {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        for channel in &channels {
+            let row = &channel.values[y * width as usize..(y + 1) * width as usize];
+            for &value in row {
+                match channel.precision {
+                    ExrPrecision::Half => writer.write_all(&to_half(value).to_le_bytes())?,
+                    ExrPrecision::Float => writer.write_all(&value.to_le_bytes())?,
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Bits of the closest 16 bit float, rounding ties to even
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Infinity stays infinity, NaN stays NaN
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        // Too large, becomes infinity
+        return sign | 0x7c00;
+    }
+
+    // Amount of mantissa bits dropped, more when the result is subnormal
+    let (half, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let shift = (14 - exponent) as u32;
+        ((mantissa | 0x80_0000) >> shift, shift)
+    } else {
+        (((exponent as u32) << 10) | (mantissa >> 13), 13)
+    };
+
+    let remainder = (mantissa | 0x80_0000) & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    // A carry out of the mantissa correctly bumps the exponent
+    let rounded = if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+        half + 1
+    } else {
+        half
+    };
+    sign | rounded as u16
+}
+
+#[test]
+fn test_to_half() {
+    assert_eq!(to_half(0.0), 0x0000);
+    assert_eq!(to_half(1.0), 0x3c00);
+    assert_eq!(to_half(-2.0), 0xc000);
+    assert_eq!(to_half(65504.0), 0x7bff);
+    assert_eq!(to_half(1e6), 0x7c00);
+    // Smallest subnormal
+    assert_eq!(to_half(5.960464e-8), 0x0001);
+    // Halfway between 1.0 and the next half, ties to even
+    assert_eq!(to_half(1.0 + 1.0 / 2048.0), 0x3c00);
+}
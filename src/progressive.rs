@@ -0,0 +1,245 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{buffer::RenderBuffer, vector::Vector3};
+
+// Limits and snapshots of a progressive render, it stops at the first limit that is
+//  reached. Without any limit it stops after the samples per pixel of the scene
+#[derive(Debug, Clone, Default)]
+pub struct Progressive {
+    // Zero still renders a pass, like one
+    pub max_passes: Option<u32>,
+    // Passes that wouldn't finish in time aren't started
+    pub time_budget: Option<Duration>,
+    // Average relative error of the pixels, see Accumulator::noise
+    pub noise_threshold: Option<f32>,
+    pub snapshot: Option<Snapshot>,
+}
+
+// Running average saved while rendering, the file is overwritten every time
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    // The extension picks the format, like with RenderBuffer::save
+    pub path: PathBuf,
+    pub interval: SnapshotInterval,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SnapshotInterval {
+    Passes(u32),
+    Time(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    MaxPasses,
+    TimeBudget,
+    Converged,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgressiveResult {
+    pub buffer: RenderBuffer,
+    pub passes: u32,
+    pub elapsed: Duration,
+    pub noise: f32,
+    pub stop_reason: StopReason,
+}
+
+impl Progressive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_passes(mut self, passes: u32) -> Self {
+        self.max_passes = Some(passes);
+        self
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn with_noise_threshold(mut self, threshold: f32) -> Self {
+        self.noise_threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_snapshots(mut self, path: impl Into<PathBuf>, interval: SnapshotInterval) -> Self {
+        self.snapshot = Some(Snapshot {
+            path: path.into(),
+            interval,
+        });
+        self
+    }
+
+    // Checked before every pass, the default passes are used when there are no limits
+    pub fn stop_reason(
+        &self,
+        accumulator: &Accumulator,
+        elapsed: Duration,
+        default_passes: u32,
+    ) -> Option<StopReason> {
+        let passes = accumulator.passes();
+        let unlimited = self.max_passes.is_none()
+            && self.time_budget.is_none()
+            && self.noise_threshold.is_none();
+        let max_passes = if unlimited {
+            Some(default_passes)
+        } else {
+            self.max_passes
+        };
+
+        // There is always at least one pass, an empty image is no use
+        if passes == 0 {
+            return None;
+        }
+        if max_passes.is_some_and(|max_passes| passes >= max_passes) {
+            return Some(StopReason::MaxPasses);
+        }
+
+        // The next pass is expected to take as long as the average
+        if let Some(budget) = self.time_budget {
+            if elapsed + elapsed / passes > budget {
+                return Some(StopReason::TimeBudget);
+            }
+        }
+
+        // The noise can't be estimated from a single pass
+        if let Some(threshold) = self.noise_threshold {
+            if passes > 1 && accumulator.noise() <= threshold {
+                return Some(StopReason::Converged);
+            }
+        }
+
+        None
+    }
+}
+
+impl SnapshotInterval {
+    // Whether to save after the given pass, with the time since the last snapshot
+    pub fn is_due(&self, passes: u32, since_last: Duration) -> bool {
+        match *self {
+            SnapshotInterval::Passes(interval) => passes % interval.max(1) == 0,
+            SnapshotInterval::Time(interval) => since_last >= interval,
+        }
+    }
+}
+
+// Running sums of all the passes so far, with the luminance kept apart to estimate
+//  the noise of every pixel
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    width: u32,
+    height: u32,
+    passes: u32,
+    sum: Vec<Vector3>,
+    luminance_sum: Vec<f32>,
+    luminance_squares: Vec<f32>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixels = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            passes: 0,
+            sum: vec![Vector3::zero(); pixels],
+            luminance_sum: vec![0.0; pixels],
+            luminance_squares: vec![0.0; pixels],
+        }
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    // Adds one color per pixel, row by row from the top left corner
+    pub fn add_pass(&mut self, colors: &[Vector3]) {
+        for (index, color) in colors.iter().enumerate() {
+            let luminance = luminance(*color);
+            self.sum[index] = self.sum[index] + *color;
+            self.luminance_sum[index] += luminance;
+            self.luminance_squares[index] += luminance * luminance;
+        }
+        self.passes += 1;
+    }
+
+    // Average of the passes so far
+    pub fn buffer(&self) -> RenderBuffer {
+        let mut buffer = RenderBuffer::new(self.width, self.height);
+        let scale = 1.0 / self.passes.max(1) as f32;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                buffer.set(
+                    x,
+                    y,
+                    self.sum[y as usize * self.width as usize + x as usize] * scale,
+                );
+            }
+        }
+        buffer
+    }
+
+    // Standard error of the average luminance relative to the luminance, averaged over
+    //  all pixels. Goes down with the square root of the passes
+    pub fn noise(&self) -> f32 {
+        if self.passes < 2 {
+            return f32::INFINITY;
+        }
+
+        let total: f32 = self
+            .luminance_sum
+            .iter()
+            .zip(&self.luminance_squares)
+            .map(|(sum, squares)| relative_error(*sum, *squares, self.passes))
+            .sum();
+        total / self.luminance_sum.len().max(1) as f32
+    }
+}
+
+pub fn luminance(color: Vector3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+// Standard error of the average of the samples relative to the average, from the sum
+//  of the samples and of their squares. Infinite with less than two samples
+pub fn relative_error(sum: f32, squares: f32, samples: u32) -> f32 {
+    if samples < 2 {
+        return f32::INFINITY;
+    }
+
+    let samples = samples as f32;
+    let mean = sum / samples;
+    let variance = ((squares - sum * mean) / (samples - 1.0)).max(0.0);
+    // The offset keeps dark pixels from dominating
+    (variance / samples).sqrt() / (mean + 0.01)
+}
+
+#[test]
+fn test_noise_goes_to_zero_for_constant_pixels() {
+    let mut accumulator = Accumulator::new(2, 1);
+    accumulator.add_pass(&[Vector3::new(0.5, 0.5, 0.5), Vector3::zero()]);
+    assert!(accumulator.noise().is_infinite());
+
+    accumulator.add_pass(&[Vector3::new(0.5, 0.5, 0.5), Vector3::zero()]);
+    assert!(accumulator.noise() < 1e-3);
+    assert_eq!(accumulator.buffer().get(0, 0).x, 0.5);
+}
+
+#[test]
+fn test_zero_max_passes_renders_one() {
+    let progressive = Progressive::new().with_max_passes(0);
+    let mut accumulator = Accumulator::new(1, 1);
+    assert_eq!(
+        progressive.stop_reason(&accumulator, Duration::ZERO, 4),
+        None
+    );
+
+    accumulator.add_pass(&[Vector3::zero()]);
+    assert_eq!(
+        progressive.stop_reason(&accumulator, Duration::ZERO, 4),
+        Some(StopReason::MaxPasses)
+    );
+}
@@ -0,0 +1,45 @@
+// Small PCG random number generator. It is seeded per pixel so renders
+//  don't depend on the order the pixels are rendered in
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        let mut random = Self { state: 0 };
+        random.next_u32();
+        random.state = random.state.wrapping_add(seed);
+        random.next_u32();
+        random
+    }
+
+    // Seed that is different for every pixel of the image
+    pub fn for_pixel(x: u32, y: u32) -> Self {
+        Self::for_pass(x, y, 0)
+    }
+
+    // Different for every pixel and every pass of a progressive render, the first
+    //  pass matches for_pixel
+    pub fn for_pass(x: u32, y: u32, pass: u32) -> Self {
+        let pass = (pass as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        Self::new((((y as u64) << 32) | x as u64) ^ pass)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        let rotation = (state >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    // Uniform number from 0 (inclusive) to 1 (exclusive)
+    pub fn next_f32(&mut self) -> f32 {
+        // Only 24 bits fit in the mantissa
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
@@ -1,12 +1,25 @@
-use image::{DynamicImage, GenericImage};
+use std::time::Instant;
+
+use image::{DynamicImage, ImageResult};
 
 use crate::{
+    adaptive::{Adaptive, AdaptiveResult},
+    aov::{Aov, AovSample, Passes},
+    assets::Assets,
+    buffer::RenderBuffer,
+    bvh::Bvh,
+    camera::{Camera, CameraSample, Eye},
+    cryptomatte::{Cryptomatte, MatteKind},
+    denoise::Denoiser,
+    filter::{Film, Filter},
     light::Light,
     material::{Color, SurfaceKind},
-    object::Intersectable,
-    object::Intersection,
     object::Object,
-    vector::Vector3,
+    object::{Intersection, MeshHit},
+    occlusion::{Ambient, AmbientOcclusion, Shading},
+    progressive::{luminance, relative_error, Accumulator, Progressive, ProgressiveResult},
+    sampler::{Sampler, SamplerKind},
+    vector::{Vector2, Vector3},
 };
 
 const SHADOW_ACNE_BIAS: f32 = 0.001;
@@ -15,6 +28,8 @@ const SHADOW_ACNE_BIAS: f32 = 0.001;
 pub struct Ray {
     pub origin: Vector3,
     pub direction: Vector3,
+    // Moment the ray was cast, within the shutter interval of the camera
+    pub time: f32,
 }
 
 impl Ray {
@@ -23,6 +38,7 @@ impl Ray {
         Ray {
             origin: intersection_point + (normal * SHADOW_ACNE_BIAS),
             direction: self.direction - normal * (self.direction.dot(&normal)) * 2.0,
+            time: self.time,
         }
     }
 }
@@ -31,156 +47,815 @@ impl Ray {
 pub struct Scene {
     pub width: u32,
     pub height: u32,
-    pub fov: f32,
+    pub camera: Camera,
+    // Rays traced per pixel, they are spread over the pixel and over the lens
+    pub samples_per_pixel: u32,
     pub max_recursion_depth: u32,
     pub lights: Vec<Light>,
     pub objects: Vec<Object>,
+    pub assets: Assets,
+    // Where the samples of a pixel go, the same numbers for the same pixel and sample
+    pub sampler: SamplerKind,
+    // Spreads the samples over the pixels around them
+    pub filter: Filter,
+    // Light added to every point, on top of the lights
+    pub ambient: Option<Ambient>,
+    pub shading: Shading,
 }
 
 impl Scene {
+    // The image is larger than the scene size when rendering in stereo
     pub fn render(&self) -> DynamicImage {
-        let mut image = DynamicImage::new_rgb8(self.width, self.height);
-        let background = Color::new(100, 100, 100);
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let ray = self.cast_prime_ray(x as f32, y as f32);
-                image.put_pixel(x, y, background.to_rgba());
-                if let Some(intersection) = self.trace_ray(&ray) {
-                    let color = self.calc_color(&ray, &intersection, 0);
-                    image.put_pixel(x, y, color.to_rgba());
+        self.render_linear().to_image()
+    }
+
+    // Unclamped linear colors, for saving to float formats
+    pub fn render_linear(&self) -> RenderBuffer {
+        self.render_passes(&[], &[]).beauty
+    }
+
+    // Renders the albedo and normals along with the image to guide the denoiser, for
+    //  previews with few samples per pixel
+    pub fn render_denoised(&self, denoiser: &Denoiser) -> RenderBuffer {
+        let passes = self.render_passes(&[Aov::Albedo, Aov::Normal], &[]);
+        denoiser.denoise_passes(&passes)
+    }
+
+    // Renders the beauty image and fills a buffer for each of the AOVs and a
+    //  cryptomatte for each of the matte kinds in the same pass
+    pub fn render_passes(&self, aovs: &[Aov], mattes: &[MatteKind]) -> Passes {
+        let bvh = self.build_bvh();
+        let (width, height) = self.camera.image_size(self.width, self.height);
+        let mut films = self.films();
+        let mut sampler = self.sampler.build(self.samples_per_pixel.max(1));
+        let mut buffers: Vec<_> = aovs
+            .iter()
+            .map(|_| RenderBuffer::new(width, height))
+            .collect();
+        let mut values = vec![Vector3::zero(); aovs.len()];
+
+        let mut cryptomattes: Vec<_> = mattes
+            .iter()
+            .map(|&kind| {
+                let names = match kind {
+                    MatteKind::Object => self.object_names(),
+                    MatteKind::Material => self.assets.material_names(),
+                };
+                Cryptomatte::new(kind, width, height, names)
+            })
+            .collect();
+        let mut coverage = vec![Vec::new(); mattes.len()];
+
+        for x in 0..width {
+            for y in 0..height {
+                let (eye, eye_x, eye_y) = self.camera.eye_pixel(self.width, self.height, x, y);
+                let film = &mut films[film_index(eye)];
+                self.render_pixel(
+                    &bvh,
+                    eye,
+                    eye_x,
+                    eye_y,
+                    sampler.as_mut(),
+                    film,
+                    aovs,
+                    &mut values,
+                    mattes,
+                    &mut coverage,
+                );
+                for (buffer, value) in buffers.iter_mut().zip(&values) {
+                    buffer.set(x, y, *value);
+                }
+                for (cryptomatte, coverage) in cryptomattes.iter_mut().zip(&coverage) {
+                    cryptomatte.set(x, y, coverage);
+                }
+            }
+        }
+
+        Passes::new(
+            self.resolve(&films),
+            aovs.iter().copied().zip(buffers).collect(),
+            cryptomattes,
+        )
+    }
+
+    // Renders one jittered sample per pixel at a time and averages them, until one of
+    //  the limits is reached. Snapshots of the average are saved along the way and
+    //  once more at the end
+    pub fn render_progressive(&self, progressive: &Progressive) -> ImageResult<ProgressiveResult> {
+        let bvh = self.build_bvh();
+        let (width, height) = self.camera.image_size(self.width, self.height);
+        let mut accumulator = Accumulator::new(width, height);
+        let start = Instant::now();
+        let mut last_snapshot = start;
+        let mut sampler = self
+            .sampler
+            .build(progressive.max_passes.unwrap_or(self.samples_per_pixel));
+
+        let stop_reason = loop {
+            if let Some(reason) =
+                progressive.stop_reason(&accumulator, start.elapsed(), self.samples_per_pixel)
+            {
+                break reason;
+            }
+
+            let pass = accumulator.passes();
+            let mut films = self.films();
+            for y in 0..height {
+                for x in 0..width {
+                    let (eye, eye_x, eye_y) = self.camera.eye_pixel(self.width, self.height, x, y);
+                    sampler.start_sample(eye_x, eye_y, pass);
+                    let sample = self.camera_sample(eye, eye_x, eye_y, sampler.as_mut(), true);
+                    let color = self.trace_camera_sample(
+                        &bvh,
+                        &sample,
+                        &mut AovSample::empty(),
+                        sampler.as_mut(),
+                    );
+                    films[film_index(eye)].add_sample(sample.pixel, color);
+                }
+            }
+            accumulator.add_pass(self.resolve(&films).pixels());
+
+            if let Some(snapshot) = &progressive.snapshot {
+                if snapshot
+                    .interval
+                    .is_due(accumulator.passes(), last_snapshot.elapsed())
+                {
+                    accumulator.buffer().save(&snapshot.path)?;
+                    last_snapshot = Instant::now();
+                }
+            }
+        };
+
+        let buffer = accumulator.buffer();
+        if let Some(snapshot) = &progressive.snapshot {
+            buffer.save(&snapshot.path)?;
+        }
+
+        Ok(ProgressiveResult {
+            buffer,
+            passes: accumulator.passes(),
+            elapsed: start.elapsed(),
+            noise: accumulator.noise(),
+            stop_reason,
+        })
+    }
+
+    // Gives every pixel as many samples as it needs to get under the error threshold,
+    //  within the limits of the settings
+    pub fn render_adaptive(&self, adaptive: &Adaptive) -> AdaptiveResult {
+        let bvh = self.build_bvh();
+        let (width, height) = self.camera.image_size(self.width, self.height);
+        let min_samples = adaptive.min_samples.max(2);
+        let max_samples = adaptive.max_samples.max(min_samples);
+        let mut films = self.films();
+        let mut sample_counts = vec![0; width as usize * height as usize];
+        let mut sampler = self.sampler.build(max_samples);
+
+        for y in 0..height {
+            for x in 0..width {
+                let (eye, eye_x, eye_y) = self.camera.eye_pixel(self.width, self.height, x, y);
+                let film = &mut films[film_index(eye)];
+                let mut luminance_sum = 0.0;
+                let mut luminance_squares = 0.0;
+                let mut samples = 0;
+
+                let mut batch = min_samples;
+                while batch > 0 {
+                    for index in samples..samples + batch {
+                        sampler.start_sample(eye_x, eye_y, index);
+                        let sample = self.camera_sample(eye, eye_x, eye_y, sampler.as_mut(), true);
+                        let color = self.trace_camera_sample(
+                            &bvh,
+                            &sample,
+                            &mut AovSample::empty(),
+                            sampler.as_mut(),
+                        );
+                        let luminance = luminance(color);
+                        film.add_sample(sample.pixel, color);
+                        luminance_sum += luminance;
+                        luminance_squares += luminance * luminance;
+                    }
+                    samples += batch;
+
+                    let error = relative_error(luminance_sum, luminance_squares, samples);
+                    batch = if error > adaptive.threshold {
+                        adaptive.batch_size.max(1).min(max_samples - samples)
+                    } else {
+                        0
+                    };
+                }
+
+                sample_counts[y as usize * width as usize + x as usize] = samples;
+            }
+        }
+
+        AdaptiveResult {
+            buffer: self.resolve(&films),
+            samples: sample_counts,
+            min_samples,
+            max_samples,
+        }
+    }
+
+    // Names in the order of the objects, unnamed objects are called after their
+    //  position, like object3
+    pub fn object_names(&self) -> Vec<String> {
+        self.objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| match &object.name {
+                Some(name) => name.clone(),
+                None => format!("object{}", index),
+            })
+            .collect()
+    }
+
+    // Splats all the samples of a pixel into the film, the AOV values are averaged into
+    //  the slice and the share of samples that hit each object or material goes to the
+    //  coverage lists. Both eyes use the same random numbers so the noise matches
+    //  between them
+    #[allow(clippy::too_many_arguments)]
+    fn render_pixel(
+        &self,
+        bvh: &Bvh,
+        eye: Option<Eye>,
+        x: u32,
+        y: u32,
+        sampler: &mut dyn Sampler,
+        film: &mut Film,
+        aovs: &[Aov],
+        values: &mut [Vector3],
+        mattes: &[MatteKind],
+        coverage: &mut [Vec<(usize, f32)>],
+    ) {
+        let samples = self.samples_per_pixel.max(1);
+        values.fill(Vector3::zero());
+        coverage.iter_mut().for_each(|coverage| coverage.clear());
+
+        for index in 0..samples {
+            // A single sample stays in the center of the pixel
+            sampler.start_sample(x, y, index);
+            let sample = self.camera_sample(eye, x, y, sampler, samples > 1);
+            let mut aov_sample = AovSample::empty();
+            let color = self.trace_camera_sample(bvh, &sample, &mut aov_sample, sampler);
+            film.add_sample(sample.pixel, color);
+
+            for (value, aov) in values.iter_mut().zip(aovs) {
+                if aov.filtered() {
+                    *value = *value + aov_sample.value(*aov);
+                } else if index == 0 {
+                    *value = aov_sample.value(*aov);
                 }
             }
+
+            for (coverage, kind) in coverage.iter_mut().zip(mattes) {
+                let hit = match kind {
+                    MatteKind::Object => aov_sample.object,
+                    MatteKind::Material => aov_sample.material,
+                };
+                if let Some(hit) = hit {
+                    match coverage.iter_mut().find(|(other, _)| *other == hit) {
+                        Some((_, amount)) => *amount += 1.0 / samples as f32,
+                        None => coverage.push((hit, 1.0 / samples as f32)),
+                    }
+                }
+            }
+        }
+
+        for (value, aov) in values.iter_mut().zip(aovs) {
+            if aov.filtered() {
+                *value = *value * (1.0 / samples as f32);
+            }
         }
-        image
     }
 
-    // Calculates the color generated by a ray
+    // One film per eye, so the samples don't bleed from one eye into the other
+    fn films(&self) -> Vec<Film> {
+        let eyes = if self.camera.stereo.is_some() { 2 } else { 1 };
+        (0..eyes)
+            .map(|_| Film::new(self.width, self.height, self.filter))
+            .collect()
+    }
+
+    // Puts the films of the eyes together into the full image
+    fn resolve(&self, films: &[Film]) -> RenderBuffer {
+        let (width, height) = self.camera.image_size(self.width, self.height);
+        let mut buffer = RenderBuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let (eye, eye_x, eye_y) = self.camera.eye_pixel(self.width, self.height, x, y);
+                buffer.set(x, y, films[film_index(eye)].get(eye_x, eye_y));
+            }
+        }
+        buffer
+    }
+
+    // Picks where the ray goes through the pixel, the lens and the shutter interval
+    fn camera_sample(
+        &self,
+        eye: Option<Eye>,
+        x: u32,
+        y: u32,
+        sampler: &mut dyn Sampler,
+        jitter: bool,
+    ) -> CameraSample {
+        let offset = if jitter {
+            sampler.next_2d()
+        } else {
+            Vector2::new(0.5, 0.5)
+        };
+        CameraSample {
+            pixel: Vector2::new(x as f32 + offset.x, y as f32 + offset.y),
+            lens: self.camera.lens.as_ref().map(|_| sampler.next_2d()),
+            time: self.camera.shutter.sample(sampler.next_1d()),
+            eye,
+        }
+    }
+
+    // Points outside of the projection stay black
+    fn trace_camera_sample(
+        &self,
+        bvh: &Bvh,
+        sample: &CameraSample,
+        aov: &mut AovSample,
+        sampler: &mut dyn Sampler,
+    ) -> Vector3 {
+        match self.camera.cast_ray(self.width, self.height, sample) {
+            Some(ray) => self.sample_color(bvh, &ray, aov, sampler),
+            None => Vector3::zero(),
+        }
+    }
+
+    // Color seen by a camera ray, what is found at the first hit goes to the AOVs
+    fn sample_color(
+        &self,
+        bvh: &Bvh,
+        ray: &Ray,
+        aov: &mut AovSample,
+        sampler: &mut dyn Sampler,
+    ) -> Vector3 {
+        // Nothing blocks the sky in ambient occlusion renders
+        let background = match self.shading {
+            Shading::Lit => Color::new(100, 100, 100).linear(),
+            Shading::AmbientOcclusion(_) => Vector3::new(1.0, 1.0, 1.0),
+        };
+        match self.trace_indexed(bvh, ray) {
+            Some((index, intersection)) => {
+                let material = intersection.material;
+                let coords = intersection.texture_coords;
+                aov.depth = -intersection.hit_point.z;
+                aov.normal = intersection.shading_normal;
+                aov.albedo = material.color(&self.assets, coords).linear() * material.albedo;
+                aov.uv = Vector3::new(coords.x, coords.y, 0.0);
+                aov.object = Some(index);
+                aov.material = Some(intersection.object.material.index());
+                match &self.shading {
+                    Shading::Lit => self.shade(bvh, ray, &intersection, 0, Some(aov), sampler),
+                    Shading::AmbientOcclusion(occlusion) => {
+                        let visibility =
+                            1.0 - self.occlusion(bvh, ray, &intersection, occlusion, sampler);
+                        aov.direct = Vector3::new(visibility, visibility, visibility);
+                        aov.direct
+                    }
+                }
+            }
+            None => background,
+        }
+    }
+
+    // Calculates the color generated by a ray, the BVH comes from build_bvh
     pub fn calc_color(
         &self,
+        bvh: &Bvh,
         ray: &Ray,
         intersection: &Intersection,
         recursion_depth: u32,
-    ) -> Color {
-        let distance = intersection.distance;
-        let object = &intersection.object;
-        let hit_point = ray.origin + (ray.direction * distance);
-        let surface_normal = object.mesh.surface_normal(hit_point);
+        sampler: &mut dyn Sampler,
+    ) -> Vector3 {
+        self.shade(bvh, ray, intersection, recursion_depth, None, sampler)
+    }
 
-        let mut color = self.calc_color_diffuse(surface_normal, hit_point, object);
+    // Splits the light between direct and reflected, the AOV gets both parts
+    fn shade(
+        &self,
+        bvh: &Bvh,
+        ray: &Ray,
+        intersection: &Intersection,
+        recursion_depth: u32,
+        aov: Option<&mut AovSample>,
+        sampler: &mut dyn Sampler,
+    ) -> Vector3 {
+        let (mut color, shadow) = self.calc_color_diffuse(bvh, ray, intersection, sampler);
+        let mut reflection = Vector3::zero();
+        let mut indirect = Vector3::zero();
 
         // cool destructuring lol
-        if let SurfaceKind::Reflective { reflectivity } = object.material.surface_kind {
+        if let SurfaceKind::Reflective { reflectivity } = intersection.material.surface_kind {
             // Cast rays until reaching max recursion depth
             // and combine the the colors
             if recursion_depth < self.max_recursion_depth {
-                let reflection_ray = ray.reflect(surface_normal, hit_point);
+                let reflection_ray =
+                    ray.reflect(intersection.shading_normal, intersection.hit_point);
 
-                let reflection_color = if let Some(intersection) = self.trace_ray(&reflection_ray) {
-                    self.calc_color(&reflection_ray, &intersection, recursion_depth + 1)
-                } else {
-                    Color::new(0, 0, 0)
-                };
+                let reflection_color =
+                    if let Some(intersection) = self.trace_ray(bvh, &reflection_ray) {
+                        self.calc_color(
+                            bvh,
+                            &reflection_ray,
+                            &intersection,
+                            recursion_depth + 1,
+                            sampler,
+                        )
+                    } else {
+                        Vector3::zero()
+                    };
 
                 color = color * (1.0 - reflectivity);
-                color = color + (reflection_color * reflectivity)
+                reflection = reflection_color;
+                indirect = reflection_color * reflectivity;
             }
         }
 
-        color
+        if let Some(aov) = aov {
+            aov.direct = color;
+            aov.indirect = indirect;
+            aov.reflection = reflection;
+            aov.shadow = shadow;
+        }
+        color + indirect
     }
 
+    // Also returns how much of the light is blocked by other objects
     fn calc_color_diffuse(
         &self,
-        surface_normal: Vector3,
-        hit_point: Vector3,
-        object: &Object,
-    ) -> Color {
+        bvh: &Bvh,
+        ray: &Ray,
+        intersection: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> (Vector3, f32) {
+        let hit_point = intersection.hit_point;
+        let material = intersection.material;
+        let surface_color = material
+            .color(&self.assets, intersection.texture_coords)
+            .linear();
         let mut color = Vector3::zero();
+        let mut total_intensity = 0.0;
+        let mut blocked_intensity = 0.0;
 
         for light in &self.lights {
             let light_direction = light.direction(hit_point);
 
             let shadow_ray = Ray {
                 // Shadow acne happens because of floating point values
-                //  so we add an offset towards the outside of the object Shadow acne happens because
-                //  of floating point values
-                //   so we add an offset towards the outside of the object
-                origin: hit_point + (surface_normal * SHADOW_ACNE_BIAS),
+                //  so we add an offset towards the outside of the object
+                origin: hit_point + (intersection.normal * SHADOW_ACNE_BIAS),
                 direction: light_direction,
+                time: ray.time,
             };
 
             // if there are no objects intersecting with the shadow ray
-            let shadow_intersection = self.trace_ray(&shadow_ray);
-            let is_in_light = shadow_intersection.is_none()
-                || shadow_intersection.unwrap().distance > light.distance(hit_point);
+            let shadow_distance = self.trace_distance(bvh, &shadow_ray);
+            let is_in_light =
+                shadow_distance.is_none() || shadow_distance.unwrap() > light.distance(hit_point);
+
+            // Amount of light that would land on the point if nothing was in the way
+            let unblocked_intensity = intersection.shading_normal.dot(&light_direction).max(0.0)
+                * light.intensity(hit_point);
+            total_intensity += unblocked_intensity;
 
-            // Amount of light that lands on the point
             let light_intensity = if is_in_light {
-                light.intensity(hit_point)
+                unblocked_intensity
             } else {
+                blocked_intensity += unblocked_intensity;
                 0.0
             };
-
-            let light_intensity = surface_normal.dot(&light_direction).max(0.0) * light_intensity;
-            let light_reflected = object.material.albedo / std::f32::consts::PI;
+            let light_reflected = material.albedo / std::f32::consts::PI;
 
             // Combine all: color of the point, color of the light, light intensity, and light reflected
-            let material = &object.material;
-            let texture_coords = object.mesh.texture_coords(hit_point);
+            let light_color = light.color().linear();
             let res_color = Vector3::new(
-                (material.color(texture_coords).red as f32 / 255.0)
-                    * (light.color().red as f32 / 255.0),
-                (material.color(texture_coords).green as f32 / 255.0)
-                    * (light.color().green as f32 / 255.0),
-                (material.color(texture_coords).blue as f32 / 255.0)
-                    * (light.color().blue as f32 / 255.0),
+                surface_color.x * light_color.x,
+                surface_color.y * light_color.y,
+                surface_color.z * light_color.z,
             );
 
-            let res_color = res_color * light_intensity * light_reflected * 255.0;
+            let res_color = res_color * light_intensity * light_reflected;
             color = color + res_color;
         }
-        Color::new(color.x as u8, color.y as u8, color.z as u8)
-    }
 
-    pub fn cast_prime_ray(&self, x: f32, y: f32) -> Ray {
-        // + 0.5 to center the ray in the pixel
-        let mut sensor_x = (x + 0.5) / self.width as f32;
-        let mut sensor_y = (y + 0.5) / self.height as f32;
-
-        // Convert coordinates to -1.0 to 1.0
-        //  and flip y axis
-        sensor_x = 2.0 * sensor_x - 1.0;
-        sensor_y = 1.0 - (2.0 * sensor_y);
-
-        // Adjust for the aspect ratio
-        if self.width > self.height {
-            let aspect_ratio = self.width as f32 / self.height as f32;
-            sensor_x *= aspect_ratio;
+        // Lambertian surface lit evenly from every side, the pi of the BRDF cancels out
+        //  over the hemisphere
+        if let Some(ambient) = &self.ambient {
+            let visibility = ambient.occlusion.as_ref().map_or(1.0, |occlusion| {
+                1.0 - self.occlusion(bvh, ray, intersection, occlusion, sampler)
+            });
+            let ambient_color = ambient.color.linear() * (ambient.intensity * visibility);
+            let res_color = Vector3::new(
+                surface_color.x * ambient_color.x,
+                surface_color.y * ambient_color.y,
+                surface_color.z * ambient_color.z,
+            );
+            color = color + res_color * material.albedo;
+        }
+
+        let shadow = if total_intensity > 0.0 {
+            blocked_intensity / total_intensity
         } else {
-            let aspect_ratio = self.height as f32 / self.width as f32;
-            sensor_y *= aspect_ratio;
+            0.0
+        };
+        (color, shadow)
+    }
+
+    // Share of the hemisphere rays that hit something within the max distance, from
+    //  0.0 for open points to 1.0 for fully enclosed ones
+    fn occlusion(
+        &self,
+        bvh: &Bvh,
+        ray: &Ray,
+        intersection: &Intersection,
+        occlusion: &AmbientOcclusion,
+        sampler: &mut dyn Sampler,
+    ) -> f32 {
+        if occlusion.samples == 0 {
+            return 0.0;
         }
 
-        // Adjust for the fov
-        let fov_adj = (self.fov.to_radians() / 2.0).tan();
-        sensor_x *= fov_adj;
-        sensor_y *= fov_adj;
+        // Rays leave from the side the camera sees
+        let normal = if intersection.shading_normal.dot(&ray.direction) > 0.0 {
+            -intersection.shading_normal
+        } else {
+            intersection.shading_normal
+        };
+        let offset = if intersection.normal.dot(&ray.direction) > 0.0 {
+            -intersection.normal
+        } else {
+            intersection.normal
+        };
 
-        Ray {
-            origin: Vector3::zero(),
-            direction: Vector3 {
-                x: sensor_x,
-                y: sensor_y,
-                z: -1.0,
+        let mut occluded = 0;
+        for _ in 0..occlusion.samples {
+            let direction = AmbientOcclusion::direction(normal, sampler.next_2d());
+            // Normal maps can tilt the hemisphere under the surface, those rays would go
+            //  into the surface itself
+            if direction.dot(&offset) <= 0.0 {
+                occluded += 1;
+                continue;
+            }
+
+            let occlusion_ray = Ray {
+                origin: intersection.hit_point + offset * SHADOW_ACNE_BIAS,
+                direction,
+                time: ray.time,
+            };
+            if self
+                .trace_distance(bvh, &occlusion_ray)
+                .is_some_and(|distance| distance < occlusion.max_distance)
+            {
+                occluded += 1;
             }
-            .normalize(),
         }
+        occluded as f32 / occlusion.samples as f32
     }
 
-    fn trace_ray(&self, ray: &Ray) -> Option<Intersection> {
-        self.objects
-            .iter()
-            .filter_map(|o| o.intersect(ray))
-            .min_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap()) // TODO: handle None
+    // Ray through the center of a pixel of the rendered image, which holds both eyes
+    //  with stereo. None where the projection doesn't cover the pixel
+    pub fn cast_prime_ray(&self, x: u32, y: u32) -> Option<Ray> {
+        let (eye, x, y) = self.camera.eye_pixel(self.width, self.height, x, y);
+        // + 0.5 to center the ray in the pixel
+        let sample = CameraSample {
+            pixel: Vector2::new(x as f32 + 0.5, y as f32 + 0.5),
+            lens: None,
+            time: self.camera.shutter.open,
+            eye,
+        };
+        self.camera.cast_ray(self.width, self.height, &sample)
+    }
+
+    // Top level structure over the objects, every render builds it once at the start so
+    //  it always matches the objects
+    pub fn build_bvh(&self) -> Bvh {
+        let bounds: Vec<_> = self.objects.iter().map(|o| o.bounds()).collect();
+        Bvh::build(&bounds)
+    }
+
+    fn trace_ray(&self, bvh: &Bvh, ray: &Ray) -> Option<Intersection<'_>> {
+        self.trace_indexed(bvh, ray)
+            .map(|(_, intersection)| intersection)
     }
+
+    // Also returns the index of the object that was hit
+    fn trace_indexed(&self, bvh: &Bvh, ray: &Ray) -> Option<(usize, Intersection<'_>)> {
+        // Only build the full hit record for the closest object
+        let mut closest: Option<(usize, f32, MeshHit)> = None;
+        bvh.closest(ray, |index| {
+            let (distance, hit) = self.objects[index].intersect_mesh(ray)?;
+            if !matches!(closest, Some((_, best, _)) if best <= distance) {
+                closest = Some((index, distance, hit));
+            }
+            Some(distance)
+        });
+
+        closest.map(|(index, distance, hit)| {
+            (
+                index,
+                self.objects[index].hit(ray, distance, &hit, &self.assets),
+            )
+        })
+    }
+
+    // Distance to the closest object, for rays that don't need shading
+    fn trace_distance(&self, bvh: &Bvh, ray: &Ray) -> Option<f32> {
+        bvh.closest(ray, |index| self.objects[index].intersect_distance(ray))
+            .map(|(_, distance)| distance)
+    }
+}
+
+// Which of the films the samples of the eye go to
+fn film_index(eye: Option<Eye>) -> usize {
+    match eye {
+        Some(Eye::Right) => 1,
+        _ => 0,
+    }
+}
+
+#[test]
+fn test_bvh_follows_the_objects() {
+    use crate::{
+        material::{Coloring, Material},
+        object::{Mesh, Sphere},
+    };
+
+    let mut assets = Assets::new();
+    let material = assets.add_material(Material::new(
+        Coloring::Color(Color::new(255, 255, 255)),
+        1.0,
+        SurfaceKind::Diffuse,
+    ));
+    let sphere = |z: f32| {
+        Object::new(
+            material,
+            Mesh::Sphere(Sphere {
+                center: Vector3::new(0.0, 0.0, z),
+                radius: 1.0,
+            }),
+        )
+    };
+    let ray = Ray {
+        origin: Vector3::zero(),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+
+    let mut scene = test_scene(assets, vec![sphere(-10.0)]);
+    assert_eq!(scene.trace_distance(&scene.build_bvh(), &ray), Some(9.0));
+
+    scene.objects.push(sphere(-5.0));
+    assert_eq!(scene.trace_distance(&scene.build_bvh(), &ray), Some(4.0));
+
+    // Moved behind the camera
+    scene.objects[1].mesh = sphere(5.0).mesh;
+    assert_eq!(scene.trace_distance(&scene.build_bvh(), &ray), Some(9.0));
+}
+
+#[cfg(test)]
+fn test_scene(assets: Assets, objects: Vec<Object>) -> Scene {
+    Scene {
+        width: 8,
+        height: 8,
+        camera: Camera::new(90.0),
+        samples_per_pixel: 1,
+        max_recursion_depth: 2,
+        lights: Vec::new(),
+        objects,
+        assets,
+        sampler: SamplerKind::default(),
+        filter: Filter::default(),
+        ambient: None,
+        shading: Shading::default(),
+    }
+}
+
+#[test]
+fn test_occlusion_in_corners() {
+    use crate::{
+        material::{Coloring, Material},
+        object::{Mesh, Plane},
+    };
+
+    let mut assets = Assets::new();
+    let material = assets.add_material(Material::new(
+        Coloring::Color(Color::new(255, 255, 255)),
+        1.0,
+        SurfaceKind::Diffuse,
+    ));
+    // Plane normals point away from the side that can be hit
+    let plane = |normal: Vector3, origin: Vector3| {
+        Object::new(material, Mesh::Plane(Plane { normal, origin }))
+    };
+    let floor = plane(Vector3::new(0.0, -1.0, 0.0), Vector3::zero());
+    let wall = plane(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.05, 0.0, 0.0));
+
+    let occlusion = AmbientOcclusion::new(64, 1.0);
+    let ray = Ray {
+        origin: Vector3::new(-1.0, 1.0, 0.0),
+        direction: Vector3::new(1.0, -1.0, 0.0).normalize(),
+        time: 0.0,
+    };
+    let occlusion_at = |objects: Vec<Object>| {
+        let scene = test_scene(assets.clone(), objects);
+        let bvh = scene.build_bvh();
+        let intersection = scene.trace_ray(&bvh, &ray).unwrap();
+        let mut sampler = SamplerKind::Sobol.build(1);
+        sampler.start_sample(0, 0, 0);
+        scene.occlusion(&bvh, &ray, &intersection, &occlusion, sampler.as_mut())
+    };
+
+    assert_eq!(occlusion_at(vec![floor.clone()]), 0.0);
+    assert!(occlusion_at(vec![floor, wall]) > 0.3);
+}
+
+#[test]
+fn test_stereo_prime_rays_converge() {
+    use crate::camera::{Stereo, StereoLayout};
+
+    let mut scene = test_scene(Assets::new(), Vec::new());
+    scene.camera = scene
+        .camera
+        .with_stereo(Stereo::new(0.1, 4.0, StereoLayout::SideBySide));
+
+    // The same pixel in each half of the image
+    let left = scene.cast_prime_ray(2, 5).unwrap();
+    let right = scene.cast_prime_ray(scene.width + 2, 5).unwrap();
+    assert!((right.origin.x - left.origin.x - 0.1).abs() < 1e-6);
+
+    let at_convergence = |ray: &Ray| ray.origin + ray.direction * (4.0 / -ray.direction.z);
+    let (left, right) = (at_convergence(&left), at_convergence(&right));
+    assert!((left.x - right.x).abs() < 1e-5 && (left.y - right.y).abs() < 1e-5);
+}
+
+#[test]
+fn test_depth_follows_the_hit_distance() {
+    use crate::{
+        material::{Coloring, Material},
+        object::{Mesh, Sphere},
+    };
+
+    let mut assets = Assets::new();
+    let material = assets.add_material(Material::new(
+        Coloring::Color(Color::new(255, 255, 255)),
+        1.0,
+        SurfaceKind::Diffuse,
+    ));
+    let sphere = Object::new(
+        material,
+        Mesh::Sphere(Sphere {
+            center: Vector3::new(0.0, 0.0, -5.0),
+            radius: 2.0,
+        }),
+    );
+    let mut scene = test_scene(assets, vec![sphere]);
+    // Odd sizes put the middle pixel right on the view axis
+    scene.width = 9;
+    scene.height = 9;
+
+    let passes = scene.render_passes(&[Aov::Depth], &[]);
+    let depth = passes.get(Aov::Depth).unwrap();
+    for (x, y) in [(4, 4), (5, 3)] {
+        let ray = scene.cast_prime_ray(x, y).unwrap();
+        let distance = scene.trace_ray(&scene.build_bvh(), &ray).unwrap().distance;
+        // Measured along the view axis, so only equal to it in the middle
+        assert!((depth.get(x, y).x - distance * -ray.direction.z).abs() < 1e-4);
+    }
+    assert!((depth.get(4, 4).x - 3.0).abs() < 1e-4);
+}
+
+#[test]
+fn test_adaptive_stops_early_on_a_flat_scene() {
+    use crate::{
+        material::{Coloring, Material},
+        object::{Mesh, Plane},
+    };
+
+    let mut assets = Assets::new();
+    let material = assets.add_material(Material::new(
+        Coloring::Color(Color::new(200, 100, 50)),
+        0.5,
+        SurfaceKind::Diffuse,
+    ));
+    let wall = Object::new(
+        material,
+        Mesh::Plane(Plane {
+            normal: Vector3::new(0.0, 0.0, -1.0),
+            origin: Vector3::new(0.0, 0.0, -5.0),
+        }),
+    );
+    // Only the ambient light, the same on every sample
+    let mut scene = test_scene(assets, vec![wall]);
+    scene.ambient = Some(Ambient::new(Color::new(255, 255, 255), 1.0));
+
+    let result = scene.render_adaptive(&Adaptive::new(4, 64, 0.01));
+    assert!(result.samples.iter().all(|&samples| samples == 4));
+    assert_eq!(result.total_samples(), 4 * 8 * 8);
+    assert!(result.buffer.get(3, 3).x > 0.0);
 }
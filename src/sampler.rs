@@ -0,0 +1,510 @@
+use std::sync::OnceLock;
+
+use crate::{random::Random, vector::Vector2};
+
+// Primes used as Halton bases, one per dimension
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+// Side of the tiled blue noise mask
+const BLUE_NOISE_SIZE: u32 = 64;
+
+// Source of the numbers used to pick samples: where they land in the pixel, on the
+//  lens, in the shutter interval. Every sample of a pixel starts again from the first
+//  dimension, and the numbers only depend on the pixel, the sample index and the
+//  dimension, so renders come out the same no matter the order
+pub trait Sampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+
+    // Uniform number from 0 (inclusive) to 1 (exclusive)
+    fn next_1d(&mut self) -> f32;
+
+    fn next_2d(&mut self) -> Vector2 {
+        let x = self.next_1d();
+        let y = self.next_1d();
+        Vector2::new(x, y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    // Plain random numbers
+    #[default]
+    Independent,
+    // Jittered strata, the samples of a pixel are spread over a grid
+    Stratified,
+    Halton,
+    // Owen scrambled Sobol
+    Sobol,
+    // Halton shifted by a blue noise mask, so the error between neighboring pixels
+    //  looks like blue noise instead of white noise
+    BlueNoise,
+}
+
+impl SamplerKind {
+    // The stratified sampler needs to know how many samples a pixel gets
+    pub fn build(self, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new()),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new()),
+            SamplerKind::Sobol => Box::new(SobolSampler::new()),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new()),
+        }
+    }
+}
+
+// Where a sampler is at, shared by all of them
+#[derive(Debug, Clone, Default)]
+struct SampleState {
+    x: u32,
+    y: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        *self = Self {
+            x,
+            y,
+            index,
+            dimension: 0,
+        };
+    }
+
+    // Seed for the current dimension, the same for every sample of the pixel
+    fn seed(&self) -> u32 {
+        hash(&[self.x, self.y, self.dimension])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    random: Random,
+}
+
+impl IndependentSampler {
+    pub fn new() -> Self {
+        Self {
+            random: Random::new(0),
+        }
+    }
+}
+
+impl Default for IndependentSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.random = Random::new(hash(&[x, y, index]) as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.random.next_f32()
+    }
+}
+
+// Every dimension is split in as many strata as samples, and 2D dimensions in a grid.
+//  Each dimension visits its strata in a different order so they don't line up
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: SampleState::default(),
+        }
+    }
+
+    // Stratum of the current sample and the jitter inside of it. Past the samples per
+    //  pixel the strata are visited again in another order
+    fn stratum(&mut self, strata: u32) -> (u32, f32) {
+        let round = self.state.index / strata;
+        let seed = hash(&[self.state.seed(), round]);
+        let stratum = permute(self.state.index % strata, strata, seed);
+        let jitter = to_unit(hash(&[seed, self.state.index]));
+        self.state.dimension += 1;
+        (stratum, jitter)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let strata = self.samples_per_pixel;
+        let (stratum, jitter) = self.stratum(strata);
+        (stratum as f32 + jitter) / strata as f32
+    }
+
+    fn next_2d(&mut self) -> Vector2 {
+        let side = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let (cell, jitter_x) = self.stratum(side * side);
+        let jitter_y = to_unit(hash(&[self.state.seed(), self.state.index]));
+        self.state.dimension += 1;
+        Vector2::new(
+            ((cell % side) as f32 + jitter_x) / side as f32,
+            ((cell / side) as f32 + jitter_y) / side as f32,
+        )
+    }
+}
+
+// Every pixel gets the same Halton sequence, rotated by a different amount in every
+//  dimension. Dimensions past the available bases are random
+#[derive(Debug, Clone, Default)]
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let seed = self.state.seed();
+        let value = match PRIMES.get(self.state.dimension as usize) {
+            Some(&base) => radical_inverse(base, self.state.index) + to_unit(seed),
+            None => to_unit(hash(&[seed, self.state.index])),
+        };
+        self.state.dimension += 1;
+        wrap(value)
+    }
+}
+
+// The first two Sobol dimensions with hash based Owen scrambling (Burley 2020). Every
+//  call gets its own scramble and shuffle of the sample order, so the dimensions
+//  don't correlate
+#[derive(Debug, Clone, Default)]
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Index into the sequence, shuffled the same way for every dimension of the call
+    fn shuffled_index(&self, seed: u32) -> u32 {
+        owen_scramble(self.state.index, hash(&[seed, 0x5eed]))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let seed = self.state.seed();
+        let index = self.shuffled_index(seed);
+        self.state.dimension += 1;
+        to_unit(owen_scramble(index.reverse_bits(), seed))
+    }
+
+    fn next_2d(&mut self) -> Vector2 {
+        let seed = self.state.seed();
+        let index = self.shuffled_index(seed);
+        self.state.dimension += 2;
+        Vector2::new(
+            to_unit(owen_scramble(index.reverse_bits(), hash(&[seed, 0]))),
+            to_unit(owen_scramble(
+                sobol_second_dimension(index),
+                hash(&[seed, 1]),
+            )),
+        )
+    }
+}
+
+// Halton sequence shifted by a tiled blue noise mask, with the tile offset differently
+//  for every dimension. Dimensions past the available bases are random, still shifted
+#[derive(Debug, Clone, Default)]
+pub struct BlueNoiseSampler {
+    state: SampleState,
+}
+
+impl BlueNoiseSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let dimension = self.state.dimension;
+        let offset = hash(&[dimension, 0xb10e]);
+        let mask_x = (self.state.x + offset) % BLUE_NOISE_SIZE;
+        let mask_y = (self.state.y + (offset >> 16)) % BLUE_NOISE_SIZE;
+        let shift = blue_noise_mask()[(mask_y * BLUE_NOISE_SIZE + mask_x) as usize];
+
+        let value = match PRIMES.get(dimension as usize) {
+            Some(&base) => radical_inverse(base, self.state.index),
+            None => to_unit(hash(&[self.state.seed(), self.state.index])),
+        };
+        self.state.dimension += 1;
+        wrap(value + shift)
+    }
+}
+
+// Ranks of a void and cluster blue noise pattern (Ulichney 1993), from 0 to 1. It is
+//  built once, the first time it's used
+fn blue_noise_mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let size = BLUE_NOISE_SIZE as i32;
+        let pixels = (size * size) as usize;
+        let radius = 6;
+        let sigma = 1.5f32;
+
+        // Gaussian energy that every set pixel spreads around, wrapping at the edges
+        let splat = |energy: &mut [f32], index: usize, sign: f32| {
+            let (x, y) = (index as i32 % size, index as i32 / size);
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let weight = (-((dx * dx + dy * dy) as f32) / (2.0 * sigma * sigma)).exp();
+                    let target =
+                        ((y + dy).rem_euclid(size) * size + (x + dx).rem_euclid(size)) as usize;
+                    energy[target] += sign * weight;
+                }
+            }
+        };
+        let extreme = |energy: &[f32], pattern: &[bool], set: bool, highest: bool| {
+            (0..pixels)
+                .filter(|&index| pattern[index] == set)
+                .reduce(|a, b| {
+                    if (energy[b] > energy[a]) == highest {
+                        b
+                    } else {
+                        a
+                    }
+                })
+                .unwrap()
+        };
+
+        // Random starting pattern with a tenth of the pixels set
+        let mut random = Random::new(0xb10e);
+        let mut pattern = vec![false; pixels];
+        let mut energy = vec![0.0; pixels];
+        let mut ones = 0;
+        while ones < pixels / 10 {
+            let index = random.next_u32() as usize % pixels;
+            if !pattern[index] {
+                pattern[index] = true;
+                splat(&mut energy, index, 1.0);
+                ones += 1;
+            }
+        }
+
+        // Move the pixel in the tightest cluster to the largest void until it stays
+        for _ in 0..pixels {
+            let cluster = extreme(&energy, &pattern, true, true);
+            pattern[cluster] = false;
+            splat(&mut energy, cluster, -1.0);
+            let void = extreme(&energy, &pattern, false, false);
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0; pixels];
+
+        // The initial pixels are ranked by taking the tightest clusters out first
+        let (mut removing, mut removing_energy) = (pattern.clone(), energy.clone());
+        for rank in (0..ones).rev() {
+            let cluster = extreme(&removing_energy, &removing, true, true);
+            removing[cluster] = false;
+            splat(&mut removing_energy, cluster, -1.0);
+            ranks[cluster] = rank;
+        }
+
+        // The rest are ranked by filling the largest voids
+        for rank in ones..pixels {
+            let void = extreme(&energy, &pattern, false, false);
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            ranks[void] = rank;
+        }
+
+        ranks
+            .iter()
+            .map(|&rank| (rank as f32 + 0.5) / pixels as f32)
+            .collect()
+    })
+}
+
+// Combines the values into a well mixed 32 bit hash
+fn hash(values: &[u32]) -> u32 {
+    values.iter().fold(0x9e37_79b9, |seed, &value| {
+        let mut x = seed ^ value.wrapping_add(0x9e37_79b9);
+        // lowbias32 by Chris Wellons
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7feb_352d);
+        x ^= x >> 15;
+        x = x.wrapping_mul(0x846c_a68b);
+        x ^= x >> 16;
+        x
+    })
+}
+
+// Number from 0 to 1 out of the top 24 bits
+fn to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+// Keeps the fractional part, for numbers from 0 to 2
+fn wrap(value: f32) -> f32 {
+    let value = if value >= 1.0 { value - 1.0 } else { value };
+    // Rounding can land exactly on 1
+    value.min(1.0 - f32::EPSILON)
+}
+
+// Mirrors the digits of the index in the given base around the decimal point
+fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    let inverse_base = 1.0 / base as f64;
+    let mut factor = inverse_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * factor;
+        index /= base;
+        factor *= inverse_base;
+    }
+    result as f32
+}
+
+// Generator matrix of the second Sobol dimension, the first one is reversing the bits
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+// Nested uniform scramble of the bits, from the most significant one down
+fn owen_scramble(value: u32, seed: u32) -> u32 {
+    // Laine and Karras permutation with the constants from Vegdahl
+    let mut x = value.reverse_bits();
+    x ^= x.wrapping_mul(0x3d20_adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x0552_6c56);
+    x ^= x.wrapping_mul(0x53a2_2864);
+    x.reverse_bits()
+}
+
+// Random permutation of the numbers below the length, from Kensler's correlated
+//  multi-jittered sampling
+fn permute(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length.max(1) - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Numbers that land past the length are permuted again until they fit
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | (seed >> 27));
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length.max(1) {
+            break;
+        }
+    }
+    (index.wrapping_add(seed)) % length.max(1)
+}
+
+#[test]
+fn test_samplers_cover_the_strata() {
+    // Every sample lands in its own stratum, checked for the first dimension
+    let samples = 16;
+    let mut samplers: Vec<Box<dyn Sampler>> = vec![
+        SamplerKind::Stratified.build(samples),
+        SamplerKind::Halton.build(samples),
+        SamplerKind::Sobol.build(samples),
+    ];
+    for sampler in &mut samplers {
+        let mut strata = vec![false; samples as usize];
+        for index in 0..samples {
+            sampler.start_sample(3, 7, index);
+            let value = sampler.next_1d();
+            assert!((0.0..1.0).contains(&value));
+            strata[(value * samples as f32) as usize] = true;
+        }
+        assert!(strata.iter().all(|&covered| covered));
+    }
+}
+
+#[test]
+fn test_samplers_cover_the_2d_strata() {
+    // With a square number of samples every cell of the grid gets one, and so does
+    //  every elementary interval of Sobol
+    let samples = 16;
+    let side = 4;
+    for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+        let mut sampler = kind.build(samples);
+        for dimension in 0..3 {
+            let mut cells = vec![false; samples as usize];
+            for index in 0..samples {
+                sampler.start_sample(3, 7, index);
+                for _ in 0..dimension {
+                    sampler.next_2d();
+                }
+                let value = sampler.next_2d();
+                let (x, y) = (
+                    (value.x * side as f32) as u32,
+                    (value.y * side as f32) as u32,
+                );
+                cells[(y * side + x) as usize] = true;
+            }
+            assert!(
+                cells.iter().all(|&covered| covered),
+                "{:?} {}",
+                kind,
+                dimension
+            );
+        }
+    }
+}
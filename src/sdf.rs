@@ -0,0 +1,364 @@
+use crate::{
+    bvh::Aabb,
+    object::{Intersectable, Shape},
+    render::Ray,
+    vector::{Vector2, Vector3},
+};
+
+// Tree of signed distance functions, negative inside the shape
+#[derive(Debug, Clone)]
+pub enum SdfNode {
+    Sphere {
+        center: Vector3,
+        radius: f32,
+    },
+    Box {
+        center: Vector3,
+        half_size: Vector3,
+    },
+    // Lies flat, around the y axis
+    Torus {
+        center: Vector3,
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    // Segment from start to end, thickened by the radius
+    Capsule {
+        start: Vector3,
+        end: Vector3,
+        radius: f32,
+    },
+    // Blends both shapes together, the smoothness is roughly the size of the seam. Zero
+    //  is a plain union
+    SmoothUnion {
+        left: Box<SdfNode>,
+        right: Box<SdfNode>,
+        smoothness: f32,
+    },
+    // Carves the right shape out of the left one with a rounded edge, or a sharp one
+    //  when the smoothness is zero
+    SmoothSubtract {
+        left: Box<SdfNode>,
+        right: Box<SdfNode>,
+        smoothness: f32,
+    },
+    // Rotates the space around the y axis by the amount (in radians) per unit of height
+    Twist {
+        child: Box<SdfNode>,
+        amount: f32,
+    },
+    // Repeats the child forever, a period of zero on an axis disables the repetition on it
+    Repeat {
+        child: Box<SdfNode>,
+        period: Vector3,
+    },
+}
+
+impl SdfNode {
+    pub fn distance(&self, point: Vector3) -> f32 {
+        match self {
+            SdfNode::Sphere { center, radius } => (point - *center).length() - radius,
+            SdfNode::Box { center, half_size } => {
+                let offset = point - *center;
+                let q = Vector3::new(
+                    offset.x.abs() - half_size.x,
+                    offset.y.abs() - half_size.y,
+                    offset.z.abs() - half_size.z,
+                );
+                let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+                outside.length() + q.x.max(q.y).max(q.z).min(0.0)
+            }
+            SdfNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let offset = point - *center;
+                let ring = (offset.x * offset.x + offset.z * offset.z).sqrt() - major_radius;
+                (ring * ring + offset.y * offset.y).sqrt() - minor_radius
+            }
+            SdfNode::Capsule { start, end, radius } => {
+                let offset = point - *start;
+                let segment = *end - *start;
+                let h = (offset.dot(&segment) / segment.dot(&segment)).clamp(0.0, 1.0);
+                (offset - segment * h).length() - radius
+            }
+            SdfNode::SmoothUnion {
+                left,
+                right,
+                smoothness,
+            } => {
+                let a = left.distance(point);
+                let b = right.distance(point);
+                if *smoothness <= 0.0 {
+                    return a.min(b);
+                }
+                let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0.0, 1.0);
+                mix(b, a, h) - smoothness * h * (1.0 - h)
+            }
+            SdfNode::SmoothSubtract {
+                left,
+                right,
+                smoothness,
+            } => {
+                let a = left.distance(point);
+                let b = right.distance(point);
+                if *smoothness <= 0.0 {
+                    return a.max(-b);
+                }
+                let h = (0.5 - 0.5 * (a + b) / smoothness).clamp(0.0, 1.0);
+                mix(a, -b, h) + smoothness * h * (1.0 - h)
+            }
+            SdfNode::Twist { child, amount } => {
+                let (sin, cos) = (amount * point.y).sin_cos();
+                child.distance(Vector3::new(
+                    cos * point.x - sin * point.z,
+                    point.y,
+                    sin * point.x + cos * point.z,
+                ))
+            }
+            SdfNode::Repeat { child, period } => {
+                let wrap = |value: f32, period: f32| {
+                    if period == 0.0 {
+                        value
+                    } else {
+                        value - period * (value / period).round()
+                    }
+                };
+                child.distance(Vector3::new(
+                    wrap(point.x, period.x),
+                    wrap(point.y, period.y),
+                    wrap(point.z, period.z),
+                ))
+            }
+        }
+    }
+}
+
+impl SdfNode {
+    // Box around the shape, None if it goes on forever
+    pub fn bounds(&self) -> Option<Aabb> {
+        let around = |center: Vector3, extent: Vector3| Aabb::new(center - extent, center + extent);
+
+        match self {
+            SdfNode::Sphere { center, radius } => {
+                Some(around(*center, Vector3::new(*radius, *radius, *radius)))
+            }
+            SdfNode::Box { center, half_size } => Some(around(*center, *half_size)),
+            SdfNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let radius = major_radius + minor_radius;
+                Some(around(*center, Vector3::new(radius, *minor_radius, radius)))
+            }
+            SdfNode::Capsule { start, end, radius } => {
+                let extent = Vector3::new(*radius, *radius, *radius);
+                Some(around(*start, extent).union(&around(*end, extent)))
+            }
+            SdfNode::SmoothUnion {
+                left,
+                right,
+                smoothness,
+            } => {
+                // The blend can bulge out a bit
+                let bounds = left.bounds()?.union(&right.bounds()?);
+                let smoothness = smoothness.max(0.0);
+                let extent = Vector3::new(smoothness, smoothness, smoothness);
+                Some(Aabb::new(bounds.min - extent, bounds.max + extent))
+            }
+            SdfNode::SmoothSubtract { left, .. } => left.bounds(),
+            SdfNode::Twist { child, .. } => {
+                // Anything the child reaches when spun around the y axis
+                let bounds = child.bounds()?;
+                let radius = [bounds.min.x.abs(), bounds.max.x.abs()]
+                    .iter()
+                    .map(|x| {
+                        let z = bounds.min.z.abs().max(bounds.max.z.abs());
+                        (x * x + z * z).sqrt()
+                    })
+                    .fold(0.0, f32::max);
+                Some(Aabb::new(
+                    Vector3::new(-radius, bounds.min.y, -radius),
+                    Vector3::new(radius, bounds.max.y, radius),
+                ))
+            }
+            SdfNode::Repeat { .. } => None,
+        }
+    }
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+// Shape defined by a distance function, rendered by sphere tracing
+#[derive(Debug, Clone)]
+pub struct Sdf {
+    pub root: SdfNode,
+    // Limits the marching to a box, taken from the shape when it is finite
+    pub bounds: Option<Aabb>,
+    pub max_steps: u32,
+    // How far rays march when the shape has no bounds to clip them to
+    pub max_distance: f32,
+    // How close to the surface counts as a hit
+    pub epsilon: f32,
+    // Fraction of the distance that is stepped each time, lower it for operators
+    //  like twist that bend the distance field
+    pub step_scale: f32,
+}
+
+impl Sdf {
+    pub fn new(root: SdfNode) -> Self {
+        Self {
+            bounds: root.bounds(),
+            root,
+            max_steps: 256,
+            max_distance: 100.0,
+            epsilon: 1e-4,
+            step_scale: 1.0,
+        }
+    }
+
+    pub fn with_bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f32) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    // The axis the normal points the most towards, used to project the texture
+    fn dominant_axis(normal: Vector3) -> usize {
+        let (x, y, z) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+        if x >= y && x >= z {
+            0
+        } else if y >= z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl Intersectable for Sdf {
+    fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        let (start, end) = match &self.bounds {
+            Some(bounds) => bounds.clip(ray, self.max_distance)?,
+            None => (0.0, self.max_distance),
+        };
+
+        // Rays that start inside the shape march towards the way out
+        let sign = self
+            .root
+            .distance(ray.origin + ray.direction * start)
+            .signum();
+
+        let mut distance = start;
+        for _ in 0..self.max_steps {
+            let step = sign * self.root.distance(ray.origin + ray.direction * distance);
+            if step < self.epsilon {
+                return Some(distance);
+            }
+
+            distance += step * self.step_scale;
+            if distance > end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+impl Shape for Sdf {
+    fn surface_normal(&self, hit_point: Vector3) -> Vector3 {
+        // Gradient of the distance field, sampled on the corners of a tetrahedron
+        let h = self.epsilon * 2.0;
+        let corners = [
+            Vector3::new(1.0, -1.0, -1.0),
+            Vector3::new(-1.0, -1.0, 1.0),
+            Vector3::new(-1.0, 1.0, -1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        ];
+        corners
+            .iter()
+            .map(|corner| *corner * self.root.distance(hit_point + *corner * h))
+            .fold(Vector3::zero(), |a, b| a + b)
+            .normalize()
+    }
+
+    fn texture_coords(&self, hit_point: Vector3) -> Vector2 {
+        // Planar projection along the axis the surface faces the most
+        match Sdf::dominant_axis(self.surface_normal(hit_point)) {
+            0 => Vector2::new(hit_point.z, hit_point.y),
+            1 => Vector2::new(hit_point.x, hit_point.z),
+            _ => Vector2::new(hit_point.x, hit_point.y),
+        }
+    }
+
+    fn tangent(&self, hit_point: Vector3) -> Vector3 {
+        match Sdf::dominant_axis(self.surface_normal(hit_point)) {
+            0 => Vector3::new(0.0, 0.0, 1.0),
+            _ => Vector3::new(1.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[test]
+fn test_sdf_hits_and_sharp_blends() {
+    let sphere = |x: f32| {
+        Box::new(SdfNode::Sphere {
+            center: Vector3::new(x, 0.0, -5.0),
+            radius: 1.0,
+        })
+    };
+    let ray = Ray {
+        origin: Vector3::zero(),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+
+    let union = Sdf::new(SdfNode::SmoothUnion {
+        left: sphere(0.0),
+        right: sphere(3.0),
+        smoothness: 0.0,
+    });
+    assert!((union.intersect_distance(&ray).unwrap() - 4.0).abs() < 1e-3);
+    let normal = union.surface_normal(Vector3::new(0.0, 0.0, -4.0));
+    assert!(normal.z > 0.99);
+
+    // Carving out a bigger sphere leaves nothing
+    let carved = SdfNode::SmoothSubtract {
+        left: sphere(0.0),
+        right: Box::new(SdfNode::Sphere {
+            center: Vector3::new(0.0, 0.0, -5.0),
+            radius: 2.0,
+        }),
+        smoothness: 0.0,
+    };
+    assert!(carved.distance(Vector3::new(0.0, 0.0, -5.0)).is_finite());
+    assert!(Sdf::new(carved).intersect_distance(&ray).is_none());
+
+    // Endless shapes only march as far as asked
+    let repeated = Sdf::new(SdfNode::Repeat {
+        child: sphere(0.0),
+        period: Vector3::new(0.0, 0.0, 50.0),
+    });
+    assert!(repeated.intersect_distance(&ray).is_some());
+    assert!(repeated
+        .with_max_distance(3.0)
+        .intersect_distance(&ray)
+        .is_none());
+}
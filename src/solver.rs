@@ -0,0 +1,176 @@
+// Real roots of a * x^2 + b * x + c = 0, sorted in ascending order
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a.abs() < 1e-12 {
+        // Degenerates into a linear equation
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        let root = -c / b;
+        return Some((root, root));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Avoid the cancellation of the textbook formula when b is close to the square root
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x0, x1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+
+    Some((x0.min(x1), x0.max(x1)))
+}
+
+// Same as solve_quadratic, for the f64 solvers that need the precision
+fn solve_quadratic_f64(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        let root = -c / b;
+        return Some((root, root));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x0, x1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+
+    Some((x0.min(x1), x0.max(x1)))
+}
+
+// Real roots of a * x^3 + b * x^2 + c * x + d = 0, in no particular order
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> [Option<f64>; 3] {
+    if a.abs() < 1e-12 {
+        // Degenerates into a quadratic equation
+        return match solve_quadratic_f64(b, c, d) {
+            Some((x0, x1)) => [Some(x0), Some(x1), None],
+            None => [None; 3],
+        };
+    }
+
+    // Normalize and substitute x = t - b / 3 to get t^3 + p * t + q = 0
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b * shift;
+    let q = 2.0 * shift.powi(3) - shift * c + d;
+
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+    if discriminant > 0.0 {
+        // One real root (Cardano)
+        let sqrt = discriminant.sqrt();
+        let t = (-q / 2.0 + sqrt).cbrt() + (-q / 2.0 - sqrt).cbrt();
+        [Some(t - shift), None, None]
+    } else if p.abs() < 1e-12 {
+        // Triple root
+        [Some(-shift), None, None]
+    } else {
+        // Three real roots (trigonometric method)
+        let radius = 2.0 * (-p / 3.0).sqrt();
+        let angle = ((3.0 * q) / (p * radius)).clamp(-1.0, 1.0).acos() / 3.0;
+        let third = 2.0 * std::f64::consts::PI / 3.0;
+        [0.0, 1.0, 2.0].map(|k| Some(radius * (angle - third * k).cos() - shift))
+    }
+}
+
+// Real roots of a * x^4 + b * x^3 + c * x^2 + d * x + e = 0, in no particular order
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> [Option<f64>; 4] {
+    if a.abs() < 1e-12 {
+        let [x0, x1, x2] = solve_cubic(b, c, d, e);
+        return [x0, x1, x2, None];
+    }
+
+    // Normalize and substitute x = y - b / 4 to get y^4 + p * y^2 + q * y + r = 0
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    let shift = b / 4.0;
+    let p = c - 6.0 * shift.powi(2);
+    let q = d - 2.0 * c * shift + 8.0 * shift.powi(3);
+    let r = e - d * shift + c * shift.powi(2) - 3.0 * shift.powi(4);
+
+    let mut roots = [None; 4];
+    let push_quadratic = |roots: &mut [Option<f64>; 4], index: usize, b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let sqrt = discriminant.sqrt();
+            roots[index] = Some((-b - sqrt) / 2.0);
+            roots[index + 1] = Some((-b + sqrt) / 2.0);
+        }
+    };
+
+    if q.abs() < 1e-12 {
+        // Biquadratic, solve for y^2
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            let sqrt = discriminant.sqrt();
+            for (index, z) in [(-p - sqrt) / 2.0, (-p + sqrt) / 2.0].iter().enumerate() {
+                if *z >= 0.0 {
+                    roots[index * 2] = Some(-z.sqrt());
+                    roots[index * 2 + 1] = Some(z.sqrt());
+                }
+            }
+        }
+    } else {
+        // Ferrari's method, using the largest root of the resolvent cubic
+        //  which is always positive when q is not zero
+        let m = solve_cubic(1.0, p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .flatten()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return roots;
+        }
+
+        let s = (2.0 * m).sqrt();
+        push_quadratic(&mut roots, 0, -s, p / 2.0 + m + s * q / (4.0 * m));
+        push_quadratic(&mut roots, 2, s, p / 2.0 + m - s * q / (4.0 * m));
+    }
+
+    // Undo the substitution and polish the roots with a few newton iterations,
+    //  the closed form loses a lot of precision
+    roots.map(|root| {
+        root.map(|y| {
+            let mut x = y - shift;
+            for _ in 0..4 {
+                let value = (((x + b) * x + c) * x + d) * x + e;
+                let derivative = ((4.0 * x + 3.0 * b) * x + 2.0 * c) * x + d;
+                if derivative.abs() < 1e-12 {
+                    break;
+                }
+                x -= value / derivative;
+            }
+            x
+        })
+    })
+}
+
+#[test]
+fn test_solve_quartic() {
+    // (x - 1)(x - 2)(x - 3)(x - 4)
+    let mut roots: Vec<f64> = solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0)
+        .into_iter()
+        .flatten()
+        .collect();
+    roots.sort_by(|a, b| a.total_cmp(b));
+
+    assert_eq!(roots.len(), 4);
+    for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+        assert!((root - expected).abs() < 1e-9);
+    }
+
+    // x^4 + 1 has no real roots
+    assert!(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0)
+        .iter()
+        .all(|root| root.is_none()));
+}
+
+#[test]
+fn test_degenerate_cubic_keeps_f64_precision() {
+    // (x - 1)(x - 1.0001), too close together for f32 to tell apart
+    let roots = solve_cubic(0.0, 1.0, -2.0001, 1.0001);
+    let (low, high) = (roots[0].unwrap(), roots[1].unwrap());
+    assert!((low - 1.0).abs() < 1e-9);
+    assert!((high - 1.0001).abs() < 1e-9);
+}
@@ -0,0 +1,125 @@
+use crate::{matrix::Matrix4, render::Ray, vector::Vector3};
+
+// Placement of an object in the world, going from object space to world space
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+    // Inverse transpose, for the normals
+    normal_matrix: Matrix4,
+}
+
+impl Transform {
+    fn new(matrix: Matrix4, inverse: Matrix4) -> Self {
+        Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Matrix4::identity(), Matrix4::identity())
+    }
+
+    // None if the matrix can't be inverted, like when scaling by zero
+    pub fn from_matrix(matrix: Matrix4) -> Option<Self> {
+        Some(Self::new(matrix, matrix.inverse()?))
+    }
+
+    // The operations are applied in the order they are chained
+    pub fn translate(self, offset: Vector3) -> Self {
+        Self::new(
+            Matrix4::translation(offset) * self.matrix,
+            self.inverse * Matrix4::translation(-offset),
+        )
+    }
+
+    // Angles in degrees around the x, y and z axes
+    pub fn rotate(self, degrees: Vector3) -> Self {
+        let rotation = Matrix4::rotation(degrees);
+        // Rotations are orthogonal
+        Self::new(rotation * self.matrix, self.inverse * rotation.transpose())
+    }
+
+    // None if any of the components is zero, the transform couldn't be inverted
+    pub fn scale(self, scale: Vector3) -> Option<Self> {
+        if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+            return None;
+        }
+        let inverse_scale = Vector3::new(1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z);
+        Some(Self::new(
+            Matrix4::scaling(scale) * self.matrix,
+            self.inverse * Matrix4::scaling(inverse_scale),
+        ))
+    }
+
+    // Applies this transform first and the other one after it
+    pub fn then(self, other: &Transform) -> Self {
+        Self::new(other.matrix * self.matrix, self.inverse * other.inverse)
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    // Moves a world space ray into object space. The direction is normalized, so the
+    //  returned factor converts object space distances back to world space ones
+    pub fn ray_to_local(&self, ray: &Ray) -> (Ray, f32) {
+        let direction = self.inverse.transform_vector(ray.direction);
+        let length = direction.length();
+        (
+            Ray {
+                origin: self.inverse.transform_point(ray.origin),
+                direction: direction * (1.0 / length),
+                time: ray.time,
+            },
+            1.0 / length,
+        )
+    }
+
+    pub fn point_to_world(&self, point: Vector3) -> Vector3 {
+        self.matrix.transform_point(point)
+    }
+
+    pub fn point_to_local(&self, point: Vector3) -> Vector3 {
+        self.inverse.transform_point(point)
+    }
+
+    pub fn vector_to_world(&self, vector: Vector3) -> Vector3 {
+        self.matrix.transform_vector(vector)
+    }
+
+    // Normals need the inverse transpose to stay perpendicular to the surface
+    //  when the scale is not uniform
+    pub fn normal_to_world(&self, normal: Vector3) -> Vector3 {
+        self.normal_matrix.transform_vector(normal).normalize()
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+#[test]
+fn test_transform_normals() {
+    // Squashed along y, a slanted normal leans further towards y
+    let transform = Transform::identity()
+        .scale(Vector3::new(1.0, 0.5, 1.0))
+        .unwrap();
+    let normal = transform.normal_to_world(Vector3::new(1.0, 1.0, 0.0).normalize());
+    assert!((normal.y / normal.x - 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn test_transform_zero_scale() {
+    assert!(Transform::identity()
+        .scale(Vector3::new(1.0, 0.0, 1.0))
+        .is_none());
+}
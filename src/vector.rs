@@ -33,13 +33,42 @@ impl Vector3 {
     }
 
     pub fn length(&self) -> f32 {
-        self.dot(&self).sqrt()
+        self.dot(self).sqrt()
     }
 
     pub fn normalize(&self) -> Vector3 {
         let length = self.length();
         Vector3::new(self.x / length, self.y / length, self.z / length)
     }
+
+    // Two unit vectors perpendicular to this one and to each other, so that
+    //  (first, second, self) is right handed. The vector must be normalized
+    pub fn basis(&self) -> (Vector3, Vector3) {
+        let helper = if self.x.abs() > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let first = helper.cross(self).normalize();
+        let second = self.cross(&first);
+        (first, second)
+    }
+
+    // Rotates the vector around the x, then y, then z axes by the given angles in degrees
+    pub fn rotate(&self, degrees: Vector3) -> Vector3 {
+        let (sin, cos) = degrees.x.to_radians().sin_cos();
+        let v = Vector3::new(
+            self.x,
+            self.y * cos - self.z * sin,
+            self.y * sin + self.z * cos,
+        );
+
+        let (sin, cos) = degrees.y.to_radians().sin_cos();
+        let v = Vector3::new(v.x * cos + v.z * sin, v.y, -v.x * sin + v.z * cos);
+
+        let (sin, cos) = degrees.z.to_radians().sin_cos();
+        Vector3::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos, v.z)
+    }
 }
 
 impl std::ops::Add<Vector3> for Vector3 {